@@ -0,0 +1,5 @@
+ALTER TABLE users
+   ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'email',
+   ADD COLUMN totp_secret TEXT,
+   ADD COLUMN pending_totp_secret TEXT,
+   ADD COLUMN totp_last_used_step BIGINT;
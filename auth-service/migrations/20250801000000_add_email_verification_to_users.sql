@@ -0,0 +1,4 @@
+-- Accounts created before addresses were verified are trusted as they are
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ALTER COLUMN email_verified SET DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN verification_sent_at BIGINT;
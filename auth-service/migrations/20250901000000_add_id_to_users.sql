@@ -0,0 +1,3 @@
+-- Existing users are given an id here; new ones are inserted with the id the service assigns
+ALTER TABLE users ADD COLUMN id UUID NOT NULL UNIQUE DEFAULT gen_random_uuid();
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS users(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT NOT NULL,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   token TEXT NOT NULL PRIMARY KEY,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   email TEXT NOT NULL PRIMARY KEY,
+   login_attempt_id TEXT NOT NULL,
+   code TEXT NOT NULL,
+   expires_at INTEGER NOT NULL
+);
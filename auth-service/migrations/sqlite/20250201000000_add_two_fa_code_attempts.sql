@@ -0,0 +1,2 @@
+ALTER TABLE two_fa_codes ADD COLUMN issued_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE two_fa_codes ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'email';
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN pending_totp_secret TEXT;
+ALTER TABLE users ADD COLUMN totp_last_used_step INTEGER;
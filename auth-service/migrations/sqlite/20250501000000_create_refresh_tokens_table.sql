@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   family_id TEXT NOT NULL,
+   expires_at INTEGER NOT NULL,
+   used BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id ON refresh_tokens(family_id);
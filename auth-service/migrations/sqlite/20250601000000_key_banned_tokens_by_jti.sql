@@ -0,0 +1,8 @@
+-- Tokens are banned by their jti now. Entries keyed by the whole token can't match
+-- any jti, and the tokens they banned no longer pass validation, so they are dropped.
+DROP TABLE IF EXISTS banned_tokens;
+
+CREATE TABLE banned_tokens(
+   jti TEXT NOT NULL PRIMARY KEY,
+   expires_at INTEGER NOT NULL
+);
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN tokens_valid_after INTEGER;
+
+-- Tokens issued before this migration count as issued at the epoch
+ALTER TABLE refresh_tokens ADD COLUMN issued_at INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS sessions(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   created_at INTEGER NOT NULL,
+   last_seen_at INTEGER NOT NULL,
+   expires_at INTEGER NOT NULL,
+   ip TEXT,
+   user_agent TEXT
+);
+
+CREATE INDEX IF NOT EXISTS sessions_email ON sessions(email);
@@ -0,0 +1,4 @@
+-- Accounts created before addresses were verified are trusted as they are.
+-- New accounts are inserted as unverified explicitly.
+ALTER TABLE users ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN verification_sent_at INTEGER;
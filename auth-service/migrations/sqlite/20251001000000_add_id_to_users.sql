@@ -0,0 +1,9 @@
+-- Existing users are given a random (version 4) UUID here; new ones are inserted with
+-- the id the service assigns
+ALTER TABLE users ADD COLUMN id TEXT;
+UPDATE users SET id = lower(
+    hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-'
+    || substr('89ab', 1 + abs(random() % 4), 1) || substr(hex(randomblob(2)), 2) || '-'
+    || hex(randomblob(6))
+);
+CREATE UNIQUE INDEX IF NOT EXISTS users_id ON users(id);
@@ -0,0 +1,4 @@
+-- Tokens issued within the cutoff's second were refused, and still are
+UPDATE users SET tokens_valid_after = tokens_valid_after * 1000 + 999 WHERE tokens_valid_after IS NOT NULL;
+
+UPDATE refresh_tokens SET issued_at = issued_at * 1000;
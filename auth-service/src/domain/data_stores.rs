@@ -1,19 +1,66 @@
+use chrono::Utc;
 use rand::distr::{Alphanumeric, SampleString};
 use uuid::Uuid;
-use crate::domain::{Email, Password};
-use crate::domain::user::User;
+use crate::domain::{Email, Password, RecoveryCode, RefreshToken, TotpSecret};
+use crate::domain::user::{NewUser, User, UserId};
+use crate::utils::constants::{
+    PASSKEY_CEREMONY_TTL_SECONDS, REFRESH_TOKEN_TTL_SECONDS, TWO_FA_CODE_TTL_SECONDS,
+};
+use webauthn_rs::prelude::{
+    AuthenticationResult, Passkey, PasskeyAuthentication, PasskeyRegistration,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    // Hashes the new user's password with Argon2id before persisting it
+    async fn add_user(&mut self, user: NewUser) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    // Finds the user whatever address they have moved to since the id was handed out
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<User, UserStoreError>;
+    // Remembers a freshly generated secret until the user confirms a code from it
+    async fn set_pending_totp_secret(&mut self, email: &Email, secret: TotpSecret) -> Result<(), UserStoreError>;
+    // Makes `secret` the user's second factor, clears the pending secret and marks
+    // `step` (the time step of the confirming code) as used
+    async fn enable_totp(&mut self, email: &Email, secret: TotpSecret, step: u64) -> Result<(), UserStoreError>;
+    // Marks a TOTP time step as used. Returns false if that step, or a later one,
+    // was already accepted, i.e. the code is being replayed.
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError>;
+    // Replaces the user's recovery codes; only their hashes are kept
+    async fn set_recovery_codes(&mut self, email: &Email, codes: &[RecoveryCode]) -> Result<(), UserStoreError>;
+    // Consumes a recovery code. Returns false if the user has no such (unused) code.
+    async fn use_recovery_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<bool, UserStoreError>;
+    // Every token issued to the user at or before `timestamp` (milliseconds since the epoch) stops working
+    async fn set_tokens_valid_after(&mut self, email: &Email, timestamp: i64) -> Result<(), UserStoreError>;
+    // Replaces the user's password, hashing the new one with Argon2id as `add_user` does
+    async fn update_password(&mut self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    // Records that the user has proven they own their address
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Records when a verification email was last sent to the user (seconds since the epoch)
+    async fn set_verification_sent_at(&mut self, email: &Email, timestamp: i64) -> Result<(), UserStoreError>;
+    // As `set_verification_sent_at`, but only for an unverified user whose last verification email
+    // went out at or before `cutoff`. Returns whether it was recorded, and false for an unknown user.
+    async fn try_set_verification_sent_at(&mut self, email: &Email, timestamp: i64, cutoff: i64) -> Result<bool, UserStoreError>;
+    // Moves the user, with everything kept about them, to `new_email`, which must not belong to anyone yet
+    async fn change_email(&mut self, email: &Email, new_email: &Email) -> Result<(), UserStoreError>;
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add(&mut self, token: String) -> ();
-    async fn contains(&self, token: &str) -> bool;
+    // Tokens are banned by their `jti`. `exp` is the token's own expiry (seconds since
+    // the epoch); there is no need to remember a banned token once it would have been
+    // rejected as expired anyway, so the entry may be evicted from then on.
+    // Returns false if the jti was banned already. The check and the ban are one step,
+    // so of two concurrent adds of the same jti only one gets true.
+    async fn add(&mut self, jti: String, exp: usize) -> Result<bool, BannedTokenStoreError>;
+    async fn contains(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
+    // Drops the entries of tokens that have expired, returning how many were removed
+    async fn purge_expired(&mut self) -> Result<u64, BannedTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BannedTokenStoreError {
+    UnexpectedError,
 }
 
 #[derive(Debug, PartialEq)]
@@ -34,10 +81,14 @@ pub trait TwoFACodeStore {
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
-    async fn get_code(
-        &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Expired entries are still returned so callers can tell an expired code from a missing one
+    async fn get_code(&self, email: &Email) -> Result<TwoFACodeEntry, TwoFACodeStoreError>;
+    // Returns how many wrong codes have been submitted for the pending login, including this one
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError>;
+    // Moves the user's pending code, if any, along with its failed attempts to `new_email`
+    async fn change_email(&mut self, email: &Email, new_email: &Email) -> Result<(), TwoFACodeStoreError>;
+    // Drops expired codes, and with them their failed attempts, returning how many were removed
+    async fn purge_expired(&mut self) -> Result<u64, TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -46,6 +97,291 @@ pub enum TwoFACodeStoreError {
     UnexpectedError,
 }
 
+// A pending 2FA code, with what is needed to expire it and to cap how often it can be guessed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoFACodeEntry {
+    pub login_attempt_id: LoginAttemptId,
+    pub code: TwoFACode,
+    // Seconds since the epoch
+    pub issued_at: i64,
+    pub ttl_seconds: u64,
+    pub failed_attempts: u32,
+}
+
+impl TwoFACodeEntry {
+    // A freshly issued code, valid for the configured TTL
+    pub fn new(login_attempt_id: LoginAttemptId, code: TwoFACode) -> Self {
+        Self {
+            login_attempt_id,
+            code,
+            issued_at: Utc::now().timestamp(),
+            ttl_seconds: *TWO_FA_CODE_TTL_SECONDS,
+            failed_attempts: 0,
+        }
+    }
+
+    pub fn expires_at(&self) -> i64 {
+        self.issued_at + self.ttl_seconds as i64
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() >= self.expires_at()
+    }
+}
+
+// Passkeys registered by users, plus the WebAuthn ceremonies waiting for an authenticator's answer
+#[async_trait::async_trait]
+pub trait CredentialStore {
+    // Credential ids are unique across all users
+    async fn add_credential(&mut self, email: &Email, passkey: Passkey) -> Result<(), CredentialStoreError>;
+    // An empty list if the user has not registered any passkey
+    async fn get_credentials(&self, email: &Email) -> Result<Vec<Passkey>, CredentialStoreError>;
+    // Persists the signature counter and backup state reported by a successful login
+    async fn update_credential(
+        &mut self,
+        email: &Email,
+        result: &AuthenticationResult,
+    ) -> Result<(), CredentialStoreError>;
+    // A newer registration replaces the user's pending one
+    async fn add_registration(
+        &mut self,
+        email: Email,
+        state: PasskeyRegistration,
+    ) -> Result<(), CredentialStoreError>;
+    // Removes the pending registration, so each challenge can only be answered once.
+    // Expired ceremonies are still returned so callers can tell them apart from missing ones.
+    async fn take_registration(
+        &mut self,
+        email: &Email,
+    ) -> Result<PasskeyCeremony<PasskeyRegistration>, CredentialStoreError>;
+    async fn add_login(
+        &mut self,
+        ceremony_id: PasskeyCeremonyId,
+        login: PasskeyLogin,
+    ) -> Result<(), CredentialStoreError>;
+    async fn take_login(
+        &mut self,
+        ceremony_id: &PasskeyCeremonyId,
+    ) -> Result<PasskeyCeremony<PasskeyLogin>, CredentialStoreError>;
+    // Drops ceremonies nobody answered in time, returning how many were removed
+    async fn purge_expired(&mut self) -> Result<u64, CredentialStoreError>;
+    // Moves the user's passkeys and ceremonies in progress to `new_email`
+    async fn change_email(&mut self, email: &Email, new_email: &Email) -> Result<(), CredentialStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CredentialStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    CeremonyNotFound,
+    UnexpectedError,
+}
+
+// A WebAuthn challenge handed to the browser, kept until the authenticator answers it
+#[derive(Debug, Clone)]
+pub struct PasskeyCeremony<S> {
+    pub state: S,
+    // Seconds since the epoch
+    pub issued_at: i64,
+}
+
+impl<S> PasskeyCeremony<S> {
+    pub fn new(state: S) -> Self {
+        Self {
+            state,
+            issued_at: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() >= self.issued_at + PASSKEY_CEREMONY_TTL_SECONDS as i64
+    }
+}
+
+// A passkey login in progress
+#[derive(Debug, Clone)]
+pub struct PasskeyLogin {
+    pub email: Email,
+    // Set when the passkey answers the 2FA challenge of a password login rather than replacing the password
+    pub login_attempt_id: Option<LoginAttemptId>,
+    pub state: PasskeyAuthentication,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PasskeyCeremonyId(String);
+
+impl PasskeyCeremonyId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match Uuid::parse_str(&id) {
+            Err(err) => Err(err.to_string()),
+            Ok(_) => Ok(PasskeyCeremonyId(id)),
+        }
+    }
+}
+
+impl Default for PasskeyCeremonyId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for PasskeyCeremonyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Refresh tokens, kept by hash. A login starts a new token family and every
+// rotation at `/refresh` adds the next token to it, so a stolen token can be
+// traced back to (and revoked along with) everything issued after it.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError>;
+    // Marks the token as rotated and returns its entry as it was before, so a token
+    // that is presented a second time comes back with `used` set
+    async fn use_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError>;
+    // Every token of the family stops working, whether it was used yet or not
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError>;
+    // Drops tokens past their expiry, used or not, returning how many were removed
+    async fn purge_expired(&mut self) -> Result<u64, RefreshTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenEntry {
+    // The user's address when the token was issued; their session has the current one
+    pub email: Email,
+    pub family_id: RefreshTokenFamilyId,
+    // Milliseconds since the epoch, to compare with `User::tokens_valid_after`
+    pub issued_at: i64,
+    // Seconds since the epoch
+    pub expires_at: i64,
+    pub used: bool,
+}
+
+impl RefreshTokenEntry {
+    // A fresh, unused token valid for the configured TTL
+    pub fn new(email: Email, family_id: RefreshTokenFamilyId) -> Self {
+        let now = Utc::now();
+
+        Self {
+            email,
+            family_id,
+            issued_at: now.timestamp_millis(),
+            expires_at: now.timestamp() + *REFRESH_TOKEN_TTL_SECONDS as i64,
+            used: false,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() >= self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshTokenFamilyId(String);
+
+impl RefreshTokenFamilyId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match Uuid::parse_str(&id) {
+            Err(err) => Err(err.to_string()),
+            Ok(_) => Ok(RefreshTokenFamilyId(id)),
+        }
+    }
+}
+
+impl Default for RefreshTokenFamilyId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for RefreshTokenFamilyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The devices a user is logged in on. A session is one login, so it is identified
+// by the refresh token family that login started, and ends along with it.
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    // Expired sessions are reported as not found
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    // The user's live sessions, in no particular order
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Records that the session was used at `timestamp` (seconds since the epoch), which keeps it alive
+    async fn touch_session(&mut self, id: &SessionId, timestamp: i64) -> Result<(), SessionStoreError>;
+    // Only removes the session if it belongs to `email`
+    async fn remove_session(&mut self, email: &Email, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+    // Moves every session of the user to `new_email`, keeping them alive
+    async fn change_email(&mut self, email: &Email, new_email: &Email) -> Result<(), SessionStoreError>;
+    // Drops sessions past their expiry, returning how many were removed
+    async fn purge_expired(&mut self) -> Result<u64, SessionStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+pub type SessionId = RefreshTokenFamilyId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    // Seconds since the epoch
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    // Where the login came from, as far as the request tells
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    // A session starting now, for the login that started the refresh token family `id`
+    pub fn new(id: SessionId, email: Email, ip: Option<String>, user_agent: Option<String>) -> Self {
+        let now = Utc::now().timestamp();
+
+        Self {
+            id,
+            email,
+            created_at: now,
+            last_seen_at: now,
+            ip,
+            user_agent,
+        }
+    }
+
+    // A session lives as long as the last refresh token issued to it
+    pub fn expires_at(&self) -> i64 {
+        self.last_seen_at + *REFRESH_TOKEN_TTL_SECONDS as i64
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() >= self.expires_at()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct LoginAttemptId(String);
 
@@ -95,3 +431,64 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_entry_is_not_expired() {
+        let entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
+
+        assert_eq!(entry.failed_attempts, 0);
+        assert_eq!(entry.ttl_seconds, *TWO_FA_CODE_TTL_SECONDS);
+        assert!(!entry.is_expired());
+    }
+
+    #[test]
+    fn test_entry_expires_after_ttl() {
+        let mut entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
+        entry.issued_at -= entry.ttl_seconds as i64;
+
+        assert!(entry.is_expired());
+    }
+
+    #[test]
+    fn test_passkey_ceremony_expires_after_ttl() {
+        let mut ceremony = PasskeyCeremony::new(());
+        assert!(!ceremony.is_expired());
+
+        ceremony.issued_at -= PASSKEY_CEREMONY_TTL_SECONDS as i64;
+        assert!(ceremony.is_expired());
+    }
+
+    #[test]
+    fn test_passkey_ceremony_id_parse() {
+        let id = PasskeyCeremonyId::default();
+
+        assert_eq!(PasskeyCeremonyId::parse(id.as_ref().to_owned()), Ok(id));
+        assert!(PasskeyCeremonyId::parse("not-a-uuid".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_refresh_token_entry_expires_after_ttl() {
+        let email = Email::parse("user@example.com").unwrap();
+        let mut entry = RefreshTokenEntry::new(email, RefreshTokenFamilyId::default());
+        assert!(!entry.used);
+        assert!(!entry.is_expired());
+
+        entry.expires_at = Utc::now().timestamp();
+        assert!(entry.is_expired());
+    }
+
+    #[test]
+    fn test_session_expires_a_refresh_token_ttl_after_last_seen() {
+        let email = Email::parse("user@example.com").unwrap();
+        let mut session = Session::new(SessionId::default(), email, None, None);
+        assert_eq!(session.created_at, session.last_seen_at);
+        assert!(!session.is_expired());
+
+        session.last_seen_at -= *REFRESH_TOKEN_TTL_SECONDS as i64;
+        assert!(session.is_expired());
+    }
+}
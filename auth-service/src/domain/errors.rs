@@ -1,3 +1,4 @@
+#[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
@@ -5,4 +6,12 @@ pub enum AuthAPIError {
     UnexpectedError,
     MissingToken,
     InvalidToken,
+    TwoFACodeExpired,
+    TooManyTwoFAAttempts,
+    TotpEnrollmentNotStarted,
+    TwoFANotEnabled,
+    PasskeyAlreadyRegistered,
+    SessionNotFound,
+    EmailNotVerified,
+    InvalidClient,
 }
@@ -0,0 +1,104 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+
+use super::Password;
+
+// An Argon2id hash of a user's password, stored as a PHC string
+// (e.g. `$argon2id$v=19$m=15000,t=2,p=1$<salt>$<hash>`). Every UserStore
+// backend persists this exact string, so hashes are portable between them.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct HashedPassword(String);
+
+impl HashedPassword {
+    // Hash a password with a fresh random salt.
+    // Argon2 is deliberately slow, so the work runs on the blocking thread pool.
+    pub async fn parse_password(password: &Password) -> Result<Self, String> {
+        let password = password.as_ref().to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            argon2()?
+                .hash_password(password.as_bytes(), &salt)
+                .map(|hash| HashedPassword(hash.to_string()))
+                .map_err(|err| err.to_string())
+        })
+        .await
+        .map_err(|err| err.to_string())?
+    }
+
+    // Wrap a PHC string loaded from storage, making sure it is a well-formed hash.
+    pub fn parse(hash: String) -> Result<Self, String> {
+        PasswordHash::new(&hash).map_err(|err| err.to_string())?;
+        Ok(HashedPassword(hash))
+    }
+
+    // Check a candidate password against this hash.
+    // The comparison of the derived hash is constant time.
+    pub async fn verify(&self, candidate: &Password) -> Result<bool, String> {
+        let hash = self.0.clone();
+        let candidate = candidate.as_ref().to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let hash = PasswordHash::new(&hash).map_err(|err| err.to_string())?;
+            match argon2()?.verify_password(candidate.as_bytes(), &hash) {
+                Ok(()) => Ok(true),
+                Err(argon2::password_hash::Error::Password) => Ok(false),
+                Err(err) => Err(err.to_string()),
+            }
+        })
+        .await
+        .map_err(|err| err.to_string())?
+    }
+}
+
+impl AsRef<str> for HashedPassword {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn argon2() -> Result<Argon2<'static>, String> {
+    let params = Params::new(15000, 2, 1, None).map_err(|err| err.to_string())?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_is_argon2id_phc_string() {
+        let password = Password::parse("password").unwrap();
+        let hash = HashedPassword::parse_password(&password).await.unwrap();
+
+        assert!(hash.as_ref().starts_with("$argon2id$v=19$"));
+        assert!(HashedPassword::parse(hash.as_ref().to_owned()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hash_is_salted() {
+        let password = Password::parse("password").unwrap();
+        let first = HashedPassword::parse_password(&password).await.unwrap();
+        let second = HashedPassword::parse_password(&password).await.unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let password = Password::parse("password").unwrap();
+        let hash = HashedPassword::parse_password(&password).await.unwrap();
+
+        assert_eq!(hash.verify(&password).await, Ok(true));
+        assert_eq!(
+            hash.verify(&Password::parse("password111").unwrap()).await,
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_plaintext() {
+        assert!(HashedPassword::parse("password".to_owned()).is_err());
+    }
+}
@@ -2,14 +2,25 @@ pub mod data_stores;
 pub mod errors;
 pub mod user;
 mod email_client;
+mod hashed_password;
+mod recovery_code;
+mod refresh_token;
+mod secret;
+mod totp;
 pub use email_client::EmailClient;
+pub use hashed_password::HashedPassword;
+pub use recovery_code::RecoveryCode;
+pub use refresh_token::RefreshToken;
+pub use secret::Secret;
+pub use totp::TotpSecret;
 
 #[derive(Clone, PartialEq, Hash, Eq, Debug)]
 pub struct Email(String);
 
 impl Email {
+    #[allow(clippy::result_unit_err)]
     pub fn parse(email: &str) -> Result<Self, ()> {
-        if email.trim().len() == 0 {
+        if email.trim().is_empty() {
             return Err(());
         }
         if !email.contains("@") {
@@ -18,9 +29,11 @@ impl Email {
 
         Ok(Email(email.to_string()))
     }
+}
 
-    pub fn to_string(self) -> String {
-        self.0.to_owned().to_string()
+impl std::fmt::Display for Email {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
@@ -33,6 +46,7 @@ impl AsRef<str> for Email {
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct Password(String);
 impl Password {
+    #[allow(clippy::result_unit_err)]
     pub fn parse(password: &str) -> Result<Self, ()> {
         if password.trim().len() < 8 {
             return Err(());
@@ -65,7 +79,7 @@ mod tests {
     #[test]
     fn test_email_parses() {
         let email = "aaa@aa.com";
-        assert!(matches!( Email::parse(email), Ok(_)))
+        assert!(Email::parse(email).is_ok())
     }
 
     #[test]
@@ -75,6 +89,6 @@ mod tests {
 
     #[test]
     fn test_password_parses() {
-        assert!(matches!( Password::parse("password"), Ok(_)))
+        assert!(Password::parse("password").is_ok())
     }
 }
@@ -0,0 +1,65 @@
+use rand::distr::{Alphanumeric, SampleString};
+use sha2::{Digest, Sha256};
+
+const TOKEN_LENGTH: usize = 43;
+
+// The opaque, long-lived token a client trades at `/refresh` for a new JWT.
+// Only the client ever holds the token itself; stores keep its hash.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    // 43 alphanumeric characters, about 256 random bits
+    pub fn generate() -> Self {
+        RefreshToken(Alphanumeric.sample_string(&mut rand::rng(), TOKEN_LENGTH))
+    }
+
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() != TOKEN_LENGTH || !token.bytes().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("Invalid refresh token".to_string());
+        }
+
+        Ok(RefreshToken(token))
+    }
+
+    // The token is random enough that a plain SHA-256 cannot be brute-forced back,
+    // and lets stores look a token up by its hash
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_token_parses() {
+        let token = RefreshToken::generate();
+
+        assert_eq!(RefreshToken::parse(token.as_ref().to_owned()), Ok(token));
+        assert_ne!(RefreshToken::generate(), RefreshToken::generate());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_tokens() {
+        assert!(RefreshToken::parse("".to_owned()).is_err());
+        assert!(RefreshToken::parse("a".repeat(TOKEN_LENGTH - 1)).is_err());
+        assert!(RefreshToken::parse(format!("{}!", "a".repeat(TOKEN_LENGTH - 1))).is_err());
+    }
+
+    #[test]
+    fn test_hash_hides_token() {
+        let token = RefreshToken::generate();
+
+        assert_eq!(token.hash().len(), 64);
+        assert!(!token.hash().contains(token.as_ref()));
+        assert_ne!(token.hash(), RefreshToken::generate().hash());
+    }
+}
@@ -0,0 +1,163 @@
+use qrcode::render::svg;
+use qrcode::QrCode;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::Email;
+use crate::utils::constants::{TOTP_ISSUER, TOTP_SKEW_STEPS, TOTP_STEP_SECONDS};
+
+// The base32-encoded RFC 6238 shared secret behind a user's authenticator app
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct TotpSecret(String);
+
+impl TotpSecret {
+    // 160 random bits, the size recommended by RFC 4226
+    pub fn generate() -> Self {
+        TotpSecret(Secret::generate_secret().to_encoded().to_string())
+    }
+
+    pub fn parse(secret: String) -> Result<Self, String> {
+        let bytes = Secret::Encoded(secret.clone())
+            .to_bytes()
+            .map_err(|err| format!("{:?}", err))?;
+        // RFC 4226: the shared secret MUST be at least 128 bits
+        if bytes.len() < 16 {
+            return Err("Secret is too short".to_string());
+        }
+
+        Ok(TotpSecret(secret))
+    }
+
+    // The `otpauth://totp/...` URI authenticator apps import the secret from
+    pub fn otpauth_uri(&self, email: &Email) -> Result<String, String> {
+        Ok(self.totp(email.as_ref())?.get_url())
+    }
+
+    // The otpauth URI rendered as a scannable QR code
+    pub fn qr_code_svg(&self, email: &Email) -> Result<String, String> {
+        let code = QrCode::new(self.otpauth_uri(email)?).map_err(|err| err.to_string())?;
+
+        Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build())
+    }
+
+    // Checks `code` against the time steps around `now` (seconds since the epoch)
+    // and returns the step it belongs to, so callers can refuse to accept it twice.
+    pub fn verify(&self, code: &str, now: u64) -> Option<u64> {
+        // The account name does not take part in the code computation
+        let totp = self.totp("").ok()?;
+        let current_step = now / TOTP_STEP_SECONDS;
+        let window = current_step.saturating_sub(TOTP_SKEW_STEPS)..=current_step + TOTP_SKEW_STEPS;
+
+        window.into_iter().find(|step| {
+            constant_time_eq(
+                totp.generate(step * TOTP_STEP_SECONDS).as_bytes(),
+                code.as_bytes(),
+            )
+        })
+    }
+
+    fn totp(&self, account_name: &str) -> Result<TOTP, String> {
+        let secret = Secret::Encoded(self.0.clone())
+            .to_bytes()
+            .map_err(|err| format!("{:?}", err))?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            TOTP_SKEW_STEPS as u8,
+            TOTP_STEP_SECONDS,
+            secret,
+            Some(TOTP_ISSUER.to_string()),
+            account_name.to_string(),
+        )
+        .map_err(|err| err.to_string())
+    }
+}
+
+impl AsRef<str> for TotpSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_at(secret: &TotpSecret, time: u64) -> String {
+        secret.totp("").unwrap().generate(time)
+    }
+
+    #[test]
+    fn test_generated_secret_parses() {
+        let secret = TotpSecret::generate();
+        assert_eq!(TotpSecret::parse(secret.as_ref().to_owned()), Ok(secret));
+    }
+
+    #[test]
+    fn test_parse_rejects_short_or_invalid_secret() {
+        assert!(TotpSecret::parse("JBSWY3DP".to_owned()).is_err());
+        assert!(TotpSecret::parse("not base32!".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let secret = TotpSecret::generate();
+        let email = Email::parse("user@example.com").unwrap();
+
+        let uri = secret.otpauth_uri(&email).unwrap();
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains(&format!("secret={}", secret.as_ref())));
+    }
+
+    #[test]
+    fn test_qr_code_svg() {
+        let secret = TotpSecret::generate();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert!(secret.qr_code_svg(&email).unwrap().contains("<svg"));
+    }
+
+    #[test]
+    fn test_verify_returns_matching_step() {
+        let secret = TotpSecret::generate();
+        let now = 1_700_000_000;
+        let step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&code_at(&secret, now), now), Some(step));
+    }
+
+    #[test]
+    fn test_verify_tolerates_clock_skew() {
+        let secret = TotpSecret::generate();
+        let now = 1_700_000_000;
+        let step = now / TOTP_STEP_SECONDS;
+        let previous = now - TOTP_STEP_SECONDS;
+        let next = now + TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&code_at(&secret, previous), now), Some(step - 1));
+        assert_eq!(secret.verify(&code_at(&secret, next), now), Some(step + 1));
+    }
+
+    #[test]
+    fn test_verify_rejects_codes_outside_window() {
+        let secret = TotpSecret::generate();
+        let now = 1_700_000_000;
+        let stale = now - (TOTP_SKEW_STEPS + 1) * TOTP_STEP_SECONDS;
+
+        let code = code_at(&secret, stale);
+        // a different step may collide on the same 6 digits, so only assert on a mismatch
+        if code != code_at(&secret, now)
+            && code != code_at(&secret, now - TOTP_STEP_SECONDS)
+            && code != code_at(&secret, now + TOTP_STEP_SECONDS)
+        {
+            assert_eq!(secret.verify(&code, now), None);
+        }
+    }
+}
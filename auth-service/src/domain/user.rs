@@ -1,18 +1,122 @@
-use crate::domain::{Email, Password};
+use crate::domain::{Email, HashedPassword, Password, TotpSecret};
+use uuid::Uuid;
 
+// A user as handed to `UserStore::add_user`, still holding the plaintext password.
 #[derive(Debug, Clone, PartialEq)]
-pub struct User {
+pub struct NewUser {
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
 }
 
+impl NewUser {
+    pub fn new(email: Email, password: Password, requires_2fa: bool) -> NewUser {
+        NewUser {
+            email,
+            password,
+            requires_2fa,
+        }
+    }
+}
+
+// A user as persisted by a `UserStore`; the password is only kept as a hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    // Assigned by `UserStore::add_user` and never changed, unlike the email
+    pub id: UserId,
+    pub email: Email,
+    pub password: HashedPassword,
+    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    // Only set once the user has confirmed a code from their authenticator app
+    pub totp_secret: Option<TotpSecret>,
+    // Handed out by `/enroll-totp` and waiting for that confirmation
+    pub pending_totp_secret: Option<TotpSecret>,
+    // The last TOTP time step accepted for this user, so a code cannot be replayed
+    pub totp_last_used_step: Option<u64>,
+    // Milliseconds since the epoch. Tokens issued at or before this time are no longer accepted.
+    pub tokens_valid_after: Option<i64>,
+    // Whether the user has followed the link emailed to their address
+    pub email_verified: bool,
+    // Seconds since the epoch, when the last verification email was sent
+    pub verification_sent_at: Option<i64>,
+}
+
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> User {
+    // A user who has just signed up, with an id of their own
+    pub fn new(email: Email, password: HashedPassword, requires_2fa: bool) -> User {
         User {
+            id: UserId::default(),
             email,
             password,
             requires_2fa,
+            two_fa_method: TwoFAMethod::default(),
+            totp_secret: None,
+            pending_totp_secret: None,
+            totp_last_used_step: None,
+            tokens_valid_after: None,
+            email_verified: false,
+            verification_sent_at: None,
+        }
+    }
+
+    // Only tokens issued after the last logout-all are accepted. `issued_at` is in
+    // milliseconds since the epoch, as `tokens_valid_after` is.
+    pub fn accepts_token_issued_at(&self, issued_at: i64) -> bool {
+        self.tokens_valid_after
+            .is_none_or(|valid_after| issued_at > valid_after)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserId(String);
+
+impl UserId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match Uuid::parse_str(&id) {
+            Err(err) => Err(err.to_string()),
+            Ok(_) => Ok(UserId(id)),
+        }
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for UserId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// How a user proves the second factor once their password checks out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TwoFAMethod {
+    // A one-off code sent by the email client
+    #[default]
+    Email,
+    // A code from an authenticator app holding the user's TOTP secret
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: &str) -> Result<Self, String> {
+        match method {
+            "email" => Ok(TwoFAMethod::Email),
+            "totp" => Ok(TwoFAMethod::Totp),
+            other => Err(format!("Unknown 2FA method: {}", other)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            TwoFAMethod::Email => "email",
+            TwoFAMethod::Totp => "totp",
         }
     }
 }
@@ -3,6 +3,13 @@ extern crate core;
 mod routes;
 pub use routes::SignUpResponse;
 pub use routes::TwoFactorAuthResponse;
+pub use routes::EnrollTotpResponse;
+pub use routes::RecoveryCodesResponse;
+pub use routes::PasskeyLoginChallenge;
+pub use routes::RotateSigningKeyResponse;
+pub use routes::SweeperMetricsResponse;
+pub use routes::IntrospectResponse;
+pub use routes::SessionsResponse;
 mod services;
 pub mod utils;
 pub mod domain;
@@ -10,16 +17,39 @@ pub mod domain;
 pub use crate::services::hashmap_user_store::HashMapUserStore;
 pub use crate::services::hashset_banned_token_store::HashSetBannedTokenStore;
 pub use crate::services::hashmap_2fa_token_store::HashMap2FaTokenStore;
+pub use crate::services::hashmap_credential_store::HashMapCredentialStore;
+pub use crate::services::hashmap_refresh_token_store::HashMapRefreshTokenStore;
+pub use crate::services::hashmap_session_store::HashMapSessionStore;
+pub use crate::services::http_email_client::{HttpEmailClient, HttpEmailConfig};
 pub use crate::services::mock_email_client::MockEmailClient;
+pub use crate::services::postgres_user_store::PostgresUserStore;
+pub use crate::services::redis_banned_token_store::RedisBannedTokenStore;
+pub use crate::services::redis_refresh_token_store::RedisRefreshTokenStore;
+pub use crate::services::redis_session_store::RedisSessionStore;
+pub use crate::services::redis_two_fa_code_store::RedisTwoFACodeStore;
+pub use crate::services::smtp_email_client::{SmtpConfig, SmtpEmailClient, SmtpTls};
+pub use crate::services::sqlite_store::SqliteStore;
 
 use crate::utils::auth::GenerateTokenError;
+use crate::utils::constants::{REQUIRE_VERIFIED_EMAIL, SWEEP_INTERVAL_SECONDS};
+use crate::utils::sweeper::{Sweeper, SweeperMetrics};
 
-use crate::domain::data_stores::{BannedTokenStore, TwoFACodeStore, UserStore};
+use crate::domain::data_stores::{
+    BannedTokenStore, CredentialStore, RefreshTokenStore, SessionStore, TwoFACodeStore, UserStore,
+};
 use crate::domain::errors::AuthAPIError;
-use axum::http::{Method, StatusCode};
+use axum::http::{header, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::{routing::post, serve::Serve, Json, Router};
+use axum::routing::{delete, get, post};
+use axum::extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo};
+use axum::middleware::AddExtension;
+use axum::{serve::Serve, Json, Router};
 use serde::{Deserialize, Serialize};
+use redis::aio::ConnectionManager;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
 use std::{error::Error, sync::Arc};
 use tokio::sync::RwLock;
 use tower_http::{cors::CorsLayer, services::ServeDir};
@@ -28,6 +58,9 @@ use crate::domain::EmailClient;
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type CredentialStoreType = Arc<RwLock<dyn CredentialStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
 
 #[derive(Clone)]
@@ -35,7 +68,13 @@ pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType
+    pub credential_store: CredentialStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub email_client: EmailClientType,
+    pub sweeper_metrics: Arc<SweeperMetrics>,
+    // Whether logins are refused until the user has verified their address
+    pub require_verified_email: bool,
 }
 
 impl AppState {
@@ -43,20 +82,30 @@ impl AppState {
         user_store: UserStoreType,
         banned_token_store: BannedStoreType,
         two_fa_code_store: TwoFACodeStoreType,
+        credential_store: CredentialStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
         email_client: EmailClientType
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
-            email_client
+            credential_store,
+            refresh_token_store,
+            session_store,
+            email_client,
+            sweeper_metrics: Arc::new(SweeperMetrics::default()),
+            require_verified_email: *REQUIRE_VERIFIED_EMAIL,
         }
     }
 }
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<IntoMakeServiceWithConnectInfo<Router, SocketAddr>, AddExtension<Router, ConnectInfo<SocketAddr>>>,
+    // Purges expired entries from the stores for as long as the server runs
+    sweeper: Sweeper,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -73,8 +122,8 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
@@ -84,28 +133,80 @@ impl Application {
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
             .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/refresh", post(routes::refresh))
             .route("/verify-2fa", post(routes::verify_2fa))
             .route("/verify-token", post(routes::verify_token))
-            .with_state(app_state)
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke))
+            .route("/password/forgot", post(routes::forgot_password))
+            .route("/password/reset", post(routes::reset_password))
+            .route("/password/change", post(routes::change_password))
+            .route("/verify-email", post(routes::verify_email))
+            .route("/verify-email/resend", post(routes::resend_verification_email))
+            .route("/email/change", post(routes::change_email))
+            .route("/email/change/confirm", post(routes::confirm_email_change))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:id", delete(routes::delete_session))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route("/admin/rotate-signing-key", post(routes::rotate_signing_key))
+            .route("/admin/sweeper-metrics", get(routes::sweeper_metrics))
+            .route("/enroll-totp", post(routes::enroll_totp))
+            .route("/confirm-totp", post(routes::confirm_totp))
+            .route("/recovery-codes", post(routes::regenerate_recovery_codes))
+            .route("/passkey/register/start", post(routes::start_passkey_registration))
+            .route("/passkey/register/finish", post(routes::finish_passkey_registration))
+            .route("/passkey/login/start", post(routes::start_passkey_login))
+            .route("/passkey/login/finish", post(routes::finish_passkey_login))
+            .with_state(app_state.clone())
             .layer(cors);
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Sessions record the address each login came from
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
+
+        let sweeper = Sweeper::spawn(app_state, Duration::from_secs(*SWEEP_INTERVAL_SECONDS));
 
         // Create a new Application instance and return it
         Ok(Application {
             server,
+            sweeper,
             address: address.to_string(),
         })
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
+        self.run_until(std::future::pending()).await
+    }
+
+    // Serve until `shutdown` completes, then let in-flight requests and the
+    // current sweep finish before returning
+    pub async fn run_until<F>(self, shutdown: F) -> Result<(), std::io::Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
         println!("listening on {}", &self.address);
-        self.server.await
+        let result = self.server.with_graceful_shutdown(shutdown).await;
+        self.sweeper.stop().await;
+        result
     }
 }
 
+pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
+    // Create a new PostgreSQL connection pool
+    PgPoolOptions::new().max_connections(5).connect(url).await
+}
+
+pub async fn get_redis_connection(redis_hostname: &str) -> redis::RedisResult<ConnectionManager> {
+    // The connection manager transparently reconnects if Redis restarts
+    let redis_url = format!("redis://{}/", redis_hostname);
+    ConnectionManager::new(redis::Client::open(redis_url)?).await
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
@@ -113,6 +214,9 @@ pub struct ErrorResponse {
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        // RFC 6749 asks for a challenge naming the scheme the client should authenticate with
+        let challenge = matches!(self, AuthAPIError::InvalidClient)
+            .then_some([(header::WWW_AUTHENTICATE, "Basic")]);
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
@@ -121,6 +225,22 @@ impl IntoResponse for AuthAPIError {
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
+            AuthAPIError::TwoFACodeExpired => (StatusCode::UNAUTHORIZED, "2FA code expired"),
+            AuthAPIError::TooManyTwoFAAttempts => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many 2FA attempts")
+            }
+            AuthAPIError::TotpEnrollmentNotStarted => {
+                (StatusCode::BAD_REQUEST, "TOTP enrollment not started")
+            }
+            AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "2FA is not enabled"),
+            AuthAPIError::PasskeyAlreadyRegistered => {
+                (StatusCode::CONFLICT, "Passkey already registered")
+            }
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthAPIError::InvalidClient => {
+                (StatusCode::UNAUTHORIZED, "Invalid client credentials")
+            }
             AuthAPIError::UnexpectedError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -129,7 +249,7 @@ impl IntoResponse for AuthAPIError {
             error: error_message.to_string(),
         });
 
-        (status, body).into_response()
+        (status, challenge, body).into_response()
     }
 }
 
@@ -1,28 +1,204 @@
-use auth_service::utils::constants::prod;
+use auth_service::domain::Secret;
+use auth_service::utils::constants::{
+    prod, DATABASE_URL, EMAIL_API_TIMEOUT_SECONDS, EMAIL_API_TOKEN, EMAIL_API_TOKEN_HEADER,
+    EMAIL_API_URL, EMAIL_CLIENT, EMAIL_SENDER, REDIS_HOST_NAME, SMTP_HOST, SMTP_PASSWORD,
+    SMTP_POOL_SIZE, SMTP_PORT, SMTP_TIMEOUT_SECONDS, SMTP_TLS, SMTP_USERNAME, SQLITE_PATH,
+    TOKEN_STORE, USER_STORE,
+};
+use auth_service::utils::signing_key::{reload_keyring_on_sighup, KEYRING};
 use auth_service::{
-    AppState, Application, HashMap2FaTokenStore, HashMapUserStore, HashSetBannedTokenStore,
-    MockEmailClient,
+    get_postgres_pool, get_redis_connection, AppState, Application, BannedStoreType,
+    EmailClientType, HashMap2FaTokenStore, HashMapCredentialStore, HashMapRefreshTokenStore,
+    HashMapSessionStore, HashMapUserStore, HashSetBannedTokenStore, HttpEmailClient,
+    HttpEmailConfig, MockEmailClient, PostgresUserStore, RedisBannedTokenStore,
+    RedisRefreshTokenStore, RedisSessionStore, RedisTwoFACodeStore, RefreshTokenStoreType,
+    SessionStoreType, SmtpConfig, SmtpEmailClient, SmtpTls, SqliteStore, TwoFACodeStoreType,
+    UserStoreType,
 };
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() {
-    let banned_user_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
-    let user_store = Arc::new(RwLock::new(HashMapUserStore::new()));
-    let two_fa_code_store = Arc::new(RwLock::new(HashMap2FaTokenStore::new()));
-    let mock_email_client = Arc::new(MockEmailClient {});
+    // Fail on a missing or malformed signing key before serving anything
+    lazy_static::initialize(&KEYRING);
+    // `kill -HUP` picks up a replaced key file
+    #[cfg(unix)]
+    tokio::spawn(reload_keyring_on_sighup());
+
+    // Users and tokens may share the SQLite file, which is opened once for both
+    let sqlite_store = if USER_STORE.as_str() == "sqlite" || TOKEN_STORE.as_str() == "sqlite" {
+        Some(configure_sqlite().await)
+    } else {
+        None
+    };
+    let (banned_user_store, two_fa_code_store, refresh_token_store, session_store) =
+        configure_token_stores(sqlite_store.clone()).await;
+    let user_store = configure_user_store(sqlite_store).await;
+    // Passkeys are only kept in memory for now
+    let credential_store = Arc::new(RwLock::new(HashMapCredentialStore::new()));
+    let email_client = configure_email_client();
 
     let app_state = AppState::new(
         user_store,
         banned_user_store,
         two_fa_code_store,
-        mock_email_client,
+        credential_store,
+        refresh_token_store,
+        session_store,
+        email_client,
     );
 
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
 
-    app.run().await.expect("Failed to run app");
+    app.run_until(shutdown_signal())
+        .await
+        .expect("Failed to run app");
+}
+
+// Ctrl-C, or the SIGTERM sent by `docker stop` and most process managers
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    println!("shutting down");
+}
+
+async fn configure_user_store(sqlite_store: Option<SqliteStore>) -> UserStoreType {
+    match USER_STORE.as_str() {
+        "memory" => Arc::new(RwLock::new(HashMapUserStore::new())),
+        "postgres" => {
+            let pg_pool = configure_postgresql().await;
+            Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)))
+        }
+        "sqlite" => Arc::new(RwLock::new(
+            sqlite_store.expect("The SQLite store is opened for USER_STORE=sqlite"),
+        )),
+        other => panic!("Unknown USER_STORE backend: {}", other),
+    }
+}
+
+// Sessions are kept alongside the tokens they hold
+async fn configure_token_stores(
+    sqlite_store: Option<SqliteStore>,
+) -> (
+    BannedStoreType,
+    TwoFACodeStoreType,
+    RefreshTokenStoreType,
+    SessionStoreType,
+) {
+    match TOKEN_STORE.as_str() {
+        "memory" => (
+            Arc::new(RwLock::new(HashSetBannedTokenStore::default())),
+            Arc::new(RwLock::new(HashMap2FaTokenStore::new())),
+            Arc::new(RwLock::new(HashMapRefreshTokenStore::new())),
+            Arc::new(RwLock::new(HashMapSessionStore::new())),
+        ),
+        "redis" => {
+            let redis_conn = configure_redis().await;
+            (
+                Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone()))),
+                Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone()))),
+                Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone()))),
+                Arc::new(RwLock::new(RedisSessionStore::new(redis_conn))),
+            )
+        }
+        "sqlite" => {
+            let sqlite_store =
+                sqlite_store.expect("The SQLite store is opened for TOKEN_STORE=sqlite");
+            (
+                Arc::new(RwLock::new(sqlite_store.clone())),
+                Arc::new(RwLock::new(sqlite_store.clone())),
+                Arc::new(RwLock::new(sqlite_store.clone())),
+                Arc::new(RwLock::new(sqlite_store)),
+            )
+        }
+        other => panic!("Unknown TOKEN_STORE backend: {}", other),
+    }
+}
+
+fn configure_email_client() -> EmailClientType {
+    match EMAIL_CLIENT.as_str() {
+        "mock" => Arc::new(MockEmailClient {}),
+        "smtp" => Arc::new(configure_smtp()),
+        "http" => Arc::new(configure_http_email()),
+        other => panic!("Unknown EMAIL_CLIENT: {}", other),
+    }
+}
+
+fn configure_smtp() -> SmtpEmailClient {
+    let tls = SmtpTls::parse(&SMTP_TLS).expect("SMTP_TLS must be starttls, tls or none.");
+
+    SmtpEmailClient::new(SmtpConfig {
+        host: SMTP_HOST.clone(),
+        port: *SMTP_PORT,
+        tls,
+        credentials: SMTP_USERNAME
+            .clone()
+            .zip(SMTP_PASSWORD.clone().map(Secret::from)),
+        sender: EMAIL_SENDER.clone(),
+        timeout: Duration::from_secs(*SMTP_TIMEOUT_SECONDS),
+        pool_size: *SMTP_POOL_SIZE,
+    })
+    .expect("Failed to configure SMTP email client")
+}
+
+fn configure_http_email() -> HttpEmailClient {
+    HttpEmailClient::new(HttpEmailConfig {
+        url: EMAIL_API_URL.clone(),
+        token_header: EMAIL_API_TOKEN_HEADER.clone(),
+        token: Secret::from(EMAIL_API_TOKEN.clone()),
+        sender: EMAIL_SENDER.clone(),
+        timeout: Duration::from_secs(*EMAIL_API_TIMEOUT_SECONDS),
+    })
+    .expect("Failed to configure HTTP email client")
+}
+
+async fn configure_postgresql() -> PgPool {
+    // Create a new database connection pool
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool!");
+
+    // Bring the schema up to date before serving requests
+    sqlx::migrate!()
+        .run(&pg_pool)
+        .await
+        .expect("Failed to run migrations");
+
+    pg_pool
+}
+
+async fn configure_sqlite() -> SqliteStore {
+    SqliteStore::open(SQLITE_PATH.as_str())
+        .await
+        .expect("Failed to open SQLite database")
+}
+
+async fn configure_redis() -> ConnectionManager {
+    get_redis_connection(&REDIS_HOST_NAME)
+        .await
+        .expect("Failed to get Redis connection")
 }
@@ -0,0 +1,76 @@
+use crate::domain::errors::AuthAPIError;
+use crate::utils::auth::validate_admin_token;
+use crate::utils::constants::ADMIN_TOKEN;
+use crate::utils::signing_key::reload_keyring;
+use crate::AppState;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+// Load the signing key from its file again, just like a SIGHUP would. A new key starts
+// signing tokens straight away; the one it replaces keeps verifying until its tokens expire.
+pub async fn rotate_signing_key(
+    headers: HeaderMap,
+) -> Result<Json<RotateSigningKeyResponse>, AuthAPIError> {
+    validate_admin_token(&headers, ADMIN_TOKEN.as_deref())?;
+
+    let status = reload_keyring().map_err(|err| {
+        eprintln!("Failed to reload signing key: {}", err);
+        AuthAPIError::UnexpectedError
+    })?;
+
+    Ok(Json(RotateSigningKeyResponse {
+        kid: status.kid,
+        retiring: status.retiring,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateSigningKeyResponse {
+    // The key new tokens are signed with
+    pub kid: String,
+    // Replaced keys that still verify the tokens they signed
+    pub retiring: Vec<String>,
+}
+
+// How much the background sweeper has purged from each store since startup
+pub async fn sweeper_metrics(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SweeperMetricsResponse>, AuthAPIError> {
+    validate_admin_token(&headers, ADMIN_TOKEN.as_deref())?;
+
+    let metrics = app_state.sweeper_metrics.snapshot();
+
+    Ok(Json(SweeperMetricsResponse {
+        runs: metrics.runs,
+        failures: metrics.failures,
+        last_run_at: metrics.last_run_at,
+        banned_tokens: metrics.banned_tokens,
+        two_fa_codes: metrics.two_fa_codes,
+        passkey_ceremonies: metrics.passkey_ceremonies,
+        refresh_tokens: metrics.refresh_tokens,
+        sessions: metrics.sessions,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SweeperMetricsResponse {
+    pub runs: u64,
+    // Purges that failed; each store is tried again on the next run
+    pub failures: u64,
+    // Seconds since the epoch, absent until the first run has finished
+    #[serde(rename = "lastRunAt")]
+    pub last_run_at: Option<i64>,
+    // Entries purged from each store
+    #[serde(rename = "bannedTokens")]
+    pub banned_tokens: u64,
+    #[serde(rename = "twoFACodes")]
+    pub two_fa_codes: u64,
+    #[serde(rename = "passkeyCeremonies")]
+    pub passkey_ceremonies: u64,
+    #[serde(rename = "refreshTokens")]
+    pub refresh_tokens: u64,
+    pub sessions: u64,
+}
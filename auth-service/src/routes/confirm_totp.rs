@@ -0,0 +1,57 @@
+use crate::domain::data_stores::UserStoreError;
+use crate::domain::errors::AuthAPIError;
+use crate::routes::recovery_codes::{issue_recovery_codes, RecoveryCodesResponse};
+use crate::utils::auth::validate_auth_cookie;
+use crate::AppState;
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+
+// Finish setting up an authenticator app: a valid code proves the app holds the
+// pending secret, which then replaces the emailed code as the user's second factor.
+// A new set of recovery codes is issued along with it.
+pub async fn confirm_totp(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Json(params): Json<ConfirmTotpParams>,
+) -> Result<(http::StatusCode, Json<RecoveryCodesResponse>), AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let mut user_store = app_state.user_store.write().await;
+
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+    let secret = user
+        .pending_totp_secret
+        .ok_or(AuthAPIError::TotpEnrollmentNotStarted)?;
+
+    let step = secret
+        .verify(&params.code, Utc::now().timestamp() as u64)
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    user_store
+        .enable_totp(&email, secret, step)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let recovery_codes = issue_recovery_codes(&mut *user_store, &email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpParams {
+    #[serde(rename = "2FACode")]
+    pub code: String,
+}
@@ -0,0 +1,154 @@
+use crate::domain::data_stores::UserStoreError;
+use crate::domain::errors::AuthAPIError;
+use crate::domain::{Email, Password};
+use crate::utils::auth::{
+    consume_email_token, generate_email_change_token, validate_auth_cookie,
+    validate_email_change_token,
+};
+use crate::utils::constants::EMAIL_CHANGE_URL;
+use crate::{AppState, EmailClientType};
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+// Ask to move the logged-in user's account to another address, which takes their
+// password. Nothing changes until the link emailed to the new address is followed,
+// and the old address is told about the request, in case it was not the user's.
+// An address that already has an account gets no link, but the response is the same,
+// so that it does not tell whether the account exists.
+pub async fn change_email(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let new_email =
+        Email::parse(&request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if new_email == email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    // A password that breaks the policy cannot be the right one
+    let password =
+        Password::parse(&request.password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let user_store = app_state.user_store.read().await;
+    match user_store.validate_user(&email, &password).await {
+        Ok(_) => {}
+        Err(UserStoreError::IncorrectCredentials) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+    match user_store.get_user(&new_email).await {
+        Ok(_) => return Ok(http::StatusCode::OK),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+    drop(user_store);
+
+    let token = generate_email_change_token(&email, &new_email)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    tokio::spawn(send_email_change_emails(
+        app_state.email_client.clone(),
+        email,
+        new_email,
+        token,
+    ));
+
+    Ok(http::StatusCode::OK)
+}
+
+async fn send_email_change_emails(
+    client: EmailClientType,
+    email: Email,
+    new_email: Email,
+    token: String,
+) {
+    let subject = "Confirm your new email address";
+    let body = format!(
+        "Follow this link to use this address for your account: {}?token={}\n\
+         If you did not ask to change your email address, you can ignore this email.",
+        *EMAIL_CHANGE_URL, token
+    );
+    if let Err(err) = client.send_email(&new_email, subject, &body).await {
+        eprintln!("Failed to send email change confirmation: {}", err);
+    }
+
+    let subject = "Your email address is about to change";
+    let body = format!(
+        "Someone asked to change the email address of your account to {}. \
+         It changes once the link sent there is followed.\n\
+         If this was not you, change your password now.",
+        new_email.as_ref()
+    );
+    if let Err(err) = client.send_email(&email, subject, &body).await {
+        eprintln!("Failed to send email change notice: {}", err);
+    }
+}
+
+// Move the account to the new address with the token from the confirmation link. The
+// user keeps their sessions, passkeys and any pending 2FA code under the new address.
+pub async fn confirm_email_change(
+    State(app_state): State<AppState>,
+    Json(request): Json<ConfirmEmailChangeRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let (claims, new_email) = validate_email_change_token(&request.token, &app_state).await?;
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Used up before the account moves, so the token cannot be replayed
+    consume_email_token(claims, &app_state.banned_token_store).await?;
+
+    let mut user_store = app_state.user_store.write().await;
+    match user_store.change_email(&email, &new_email).await {
+        Ok(()) => {}
+        // Taken by a signup since the change was asked for
+        Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+    // Following the link proves the new address works
+    user_store
+        .mark_email_verified(&new_email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+
+    app_state
+        .two_fa_code_store
+        .write()
+        .await
+        .change_email(&email, &new_email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    app_state
+        .session_store
+        .write()
+        .await
+        .change_email(&email, &new_email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    app_state
+        .credential_store
+        .write()
+        .await
+        .change_email(&email, &new_email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(http::StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
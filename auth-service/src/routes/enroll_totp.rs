@@ -0,0 +1,52 @@
+use crate::domain::errors::AuthAPIError;
+use crate::domain::TotpSecret;
+use crate::utils::auth::validate_auth_cookie;
+use crate::AppState;
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+// Start setting up an authenticator app for the logged-in user. The secret only
+// becomes their second factor once `/confirm-totp` has seen a code generated from it.
+pub async fn enroll_totp(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(http::StatusCode, Json<EnrollTotpResponse>), AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let secret = TotpSecret::generate();
+    let (Ok(otpauth_uri), Ok(qr_code_svg)) =
+        (secret.otpauth_uri(&email), secret.qr_code_svg(&email))
+    else {
+        return Err(AuthAPIError::UnexpectedError);
+    };
+
+    app_state
+        .user_store
+        .write()
+        .await
+        .set_pending_totp_secret(&email, secret.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(EnrollTotpResponse {
+            otpauth_uri,
+            qr_code_svg,
+            secret: secret.as_ref().to_owned(),
+        }),
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    #[serde(rename = "qrCodeSvg")]
+    pub qr_code_svg: String,
+    // For apps that cannot scan the QR code
+    pub secret: String,
+}
@@ -0,0 +1,95 @@
+use crate::domain::errors::AuthAPIError;
+use crate::utils::auth::{validate_client_credentials, validate_unbanned_token, Claims};
+use crate::utils::constants::{ACCESS_TOKEN_SCOPE, CLIENT_CREDENTIALS};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::{Form, Json};
+use serde::{Deserialize, Serialize};
+
+// RFC 7662 token introspection, for trusted services that would rather ask than decode the JWT.
+// Only access tokens are known here; refresh tokens, like any token that is invalid, banned
+// or whose user is gone, are reported as inactive without saying why.
+pub async fn introspect(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Form(params): Form<IntrospectParams>,
+) -> Result<Json<IntrospectResponse>, AuthAPIError> {
+    validate_client_credentials(&headers, &CLIENT_CREDENTIALS)?;
+
+    let (claims, user) = match validate_unbanned_token(&params.token, &app_state).await {
+        Ok(validated) => validated,
+        Err(AuthAPIError::InvalidToken) => return Ok(Json(IntrospectResponse::inactive())),
+        Err(err) => return Err(err),
+    };
+
+    Ok(Json(IntrospectResponse {
+        username: Some(user.email.as_ref().to_owned()),
+        two_fa_enabled: Some(user.requires_2fa),
+        two_fa_method: user
+            .requires_2fa
+            .then(|| user.two_fa_method.as_ref().to_owned()),
+        ..IntrospectResponse::active(claims)
+    }))
+}
+
+// A `token_type_hint` may be sent too, but with only access tokens to look at it changes nothing
+#[derive(Deserialize)]
+pub struct IntrospectParams {
+    pub token: String,
+}
+
+// Field names follow RFC 7662, which is why they are not camelCase like our other responses
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    // The user's id; their email is given as `username`, as it may change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    // Whether logging in as the user takes a second factor, and which one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_fa_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_fa_method: Option<String>,
+}
+
+impl IntrospectResponse {
+    // RFC 7662 section 2.2: nothing but `active` may be given away about an inactive token
+    fn inactive() -> Self {
+        Self::default()
+    }
+
+    fn active(claims: Claims) -> Self {
+        Self {
+            active: true,
+            scope: Some(ACCESS_TOKEN_SCOPE.to_owned()),
+            token_type: Some("Bearer".to_owned()),
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            ..Self::default()
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use crate::utils::signing_key::keyring;
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+// The public keys tokens are signed with, including keys that are being rotated out,
+// so other services can verify tokens without calling `/verify-token`
+pub async fn jwks() -> Json<JwkSet> {
+    Json(keyring().jwk_set())
+}
@@ -1,19 +1,20 @@
 use crate::domain::data_stores::{LoginAttemptId, TwoFACode, UserStoreError};
 use crate::domain::errors::AuthAPIError;
-use crate::domain::user::User;
+use crate::domain::user::{TwoFAMethod, User};
 use crate::domain::{Email, Password};
 use crate::routes::login::LoginResponse::{RegularAuth, TwoFactorAuth};
-use crate::utils::auth::{generate_auth_cookie, GenerateTokenError};
+use crate::utils::auth::{add_login_cookies, check_email_verified};
+use crate::utils::client_info::ClientInfo;
 use crate::{AppState, EmailClientType, TwoFACodeStoreType};
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::{http, Json};
 use axum_extra::extract::CookieJar;
-use log::log;
 use serde::{Deserialize, Serialize};
 
 pub async fn login(
     State(app_state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(params): Json<LoginParams>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -38,6 +39,11 @@ pub async fn login(
         }
         Err(_) => (jar, Err(AuthAPIError::UnexpectedError)),
         Ok(user) => {
+            // Checked before a 2FA code is sent for a login that cannot succeed
+            if let Err(err) = check_email_verified(&user, &app_state) {
+                return (jar, Err(err));
+            }
+
             if user.requires_2fa {
                 handle_2fa(
                     app_state.two_fa_code_store,
@@ -47,7 +53,7 @@ pub async fn login(
                 )
                 .await
             } else {
-                handle_no_2fa(jar, &user.email).await
+                handle_no_2fa(jar, &app_state, &user.email, client).await
             }
         }
     }
@@ -64,7 +70,11 @@ async fn handle_2fa(
 ) {
     let (login_attempt_id, two_fa_code) = (LoginAttemptId::default(), TwoFACode::default());
 
-    if let Err(_) = send_2fa_email(email_client, &user.email, &two_fa_code).await {
+    // Users with an authenticator app read their code from it, so nothing is sent.
+    // The login attempt is still stored (with a code that is never used) to carry its expiry and attempt count.
+    if user.two_fa_method == TwoFAMethod::Email
+        && send_2fa_email(email_client, &user.email, &two_fa_code).await.is_err()
+    {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
@@ -101,27 +111,16 @@ async fn send_2fa_email(client: EmailClientType, email: &Email, code: &TwoFACode
 
 async fn handle_no_2fa(
     jar: CookieJar,
+    app_state: &AppState,
     email: &Email,
+    client: ClientInfo,
 ) -> (
     CookieJar,
     Result<(http::StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    // generate cookie
-    let auth_cookie = generate_auth_cookie(&email).map_err(|err| {
-        match err {
-            GenerateTokenError::UnexpectedError => AuthAPIError::UnexpectedError,
-            GenerateTokenError::TokenError(_) => {
-                // TODO: generate log entry for token error
-                AuthAPIError::UnexpectedError
-            }
-        }
-    });
-
-    match auth_cookie {
-        Ok(cookie) => (
-            jar.add(cookie),
-            Ok((http::StatusCode::OK, Json(RegularAuth))),
-        ),
+    // generate the JWT and refresh token cookies
+    match add_login_cookies(jar.clone(), app_state, email, client).await {
+        Ok(jar) => (jar, Ok((http::StatusCode::OK, Json(RegularAuth)))),
         Err(err) => (jar, Err(err)),
     }
 }
@@ -1,7 +1,9 @@
+use crate::domain::data_stores::{SessionId, SessionStoreError};
 use crate::domain::errors::AuthAPIError;
-use crate::utils::auth::validate_token;
-use crate::utils::constants::JWT_COOKIE_NAME;
-use crate::{AppState};
+use crate::domain::RefreshToken;
+use crate::utils::auth::{revoke_access_token, revoke_refresh_token, validate_token};
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::AppState;
 use axum::extract::State;
 use axum::http;
 use axum::response::IntoResponse;
@@ -18,12 +20,42 @@ pub async fn logout(
     let cookie = cookie.to_owned();
     let token = cookie.value().to_owned();
 
-    if let Err(_) = validate_token(&token).await {
+    let (claims, user) = match validate_token(&token, &app_state.user_store).await {
+        Ok(validated) => validated,
+        Err(err) => return (jar, Err(err)),
+    };
+
+    // The session ends along with its tokens
+    let Ok(session_id) = SessionId::parse(claims.sid.clone()) else {
         return (jar, Err(AuthAPIError::InvalidToken));
+    };
+    match app_state
+        .session_store
+        .write()
+        .await
+        .remove_session(&user.email, &session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     }
 
-    app_state.banned_token_store.write().await.add(token).await;
+    if let Err(err) = revoke_access_token(claims, &app_state.banned_token_store).await {
+        return (jar, Err(err));
+    }
 
     let jar = jar.remove(cookie);
-    (jar, Ok(http::StatusCode::OK))
+
+    // The refresh token, and any token rotated from the same login, goes with it
+    let Some(refresh_cookie) = jar.get(REFRESH_COOKIE_NAME).map(|cookie| cookie.to_owned()) else {
+        return (jar, Ok(http::StatusCode::OK));
+    };
+    if let Ok(refresh_token) = RefreshToken::parse(refresh_cookie.value().to_owned()) {
+        if let Err(err) = revoke_refresh_token(&refresh_token, &app_state.refresh_token_store).await
+        {
+            return (jar, Err(err));
+        }
+    }
+
+    (jar.remove(refresh_cookie), Ok(http::StatusCode::OK))
 }
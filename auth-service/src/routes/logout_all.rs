@@ -0,0 +1,31 @@
+use crate::domain::errors::AuthAPIError;
+use crate::utils::auth::{revoke_all_sessions, validate_auth_cookie};
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::AppState;
+use axum::extract::State;
+use axum::http;
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::CookieJar;
+
+// Log the user out of every session at once, say after their password was compromised.
+// Rather than banning tokens one by one, every access and refresh token issued to the
+// user up to now stops being accepted, including ones this service has never seen again.
+pub async fn logout_all(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
+    let user = match validate_auth_cookie(&jar, &app_state).await {
+        Ok((_, user)) => user,
+        Err(err) => return (jar, Err(err)),
+    };
+
+    if let Err(err) = revoke_all_sessions(&user.email, &app_state).await {
+        return (jar, Err(err));
+    }
+
+    let jar = jar
+        .remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+        .remove(Cookie::build(REFRESH_COOKIE_NAME).path("/"));
+
+    (jar, Ok(http::StatusCode::OK))
+}
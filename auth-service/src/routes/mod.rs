@@ -1,11 +1,41 @@
+mod admin;
+mod confirm_totp;
+mod email;
+mod enroll_totp;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod logout_all;
+mod passkey_login;
+mod passkey_register;
+mod password;
+mod recovery_codes;
+mod refresh;
+mod revoke;
+mod sessions;
 mod signup;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use admin::*;
+pub use confirm_totp::*;
+pub use email::*;
+pub use enroll_totp::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use logout_all::*;
+pub use passkey_login::*;
+pub use passkey_register::*;
+pub use password::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use revoke::*;
+pub use sessions::*;
 pub use signup::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
@@ -0,0 +1,171 @@
+use crate::domain::data_stores::{
+    CredentialStoreError, LoginAttemptId, PasskeyCeremonyId, PasskeyLogin, TwoFACodeStoreError,
+};
+use crate::domain::errors::AuthAPIError;
+use crate::domain::Email;
+use crate::utils::auth::add_login_cookies;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::webauthn::WEBAUTHN;
+use crate::{AppState, TwoFACodeStoreType};
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{PublicKeyCredential, RequestChallengeResponse};
+
+// Challenge one of the user's passkeys. Without a `loginAttemptId` the passkey replaces
+// the password altogether; with one it answers the 2FA step of a password login instead.
+pub async fn start_passkey_login(
+    State(app_state): State<AppState>,
+    Json(params): Json<PasskeyLoginStartParams>,
+) -> Result<Json<PasskeyLoginChallenge>, AuthAPIError> {
+    let email = Email::parse(&params.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = params
+        .login_attempt_id
+        .map(LoginAttemptId::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if let Some(login_attempt_id) = &login_attempt_id {
+        check_login_attempt(&app_state.two_fa_code_store, &email, login_attempt_id).await?;
+    }
+
+    let mut credential_store = app_state.credential_store.write().await;
+
+    let passkeys = credential_store
+        .get_credentials(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Same answer as a wrong password, so this does not reveal who has an account
+    if passkeys.is_empty() {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let (challenge, state) = WEBAUTHN
+        .start_passkey_authentication(&passkeys)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let ceremony_id = PasskeyCeremonyId::default();
+    credential_store
+        .add_login(
+            ceremony_id.clone(),
+            PasskeyLogin {
+                email,
+                login_attempt_id,
+                state,
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(PasskeyLoginChallenge {
+        ceremony_id: ceremony_id.as_ref().to_owned(),
+        challenge,
+    }))
+}
+
+// Check the authenticator's signature and log the user in
+pub async fn finish_passkey_login(
+    State(app_state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(params): Json<PasskeyLoginFinishParams>,
+) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
+    let Ok(ceremony_id) = PasskeyCeremonyId::parse(params.ceremony_id) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    let mut credential_store = app_state.credential_store.write().await;
+
+    let login = match credential_store.take_login(&ceremony_id).await {
+        Ok(ceremony) if !ceremony.is_expired() => ceremony.state,
+        Ok(_) | Err(CredentialStoreError::CeremonyNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let Ok(result) = WEBAUTHN.finish_passkey_authentication(&params.credential, &login.state)
+    else {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    };
+
+    if credential_store
+        .update_credential(&login.email, &result)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    drop(credential_store);
+
+    // The pending 2FA login is used up, just as if its code had been entered
+    if let Some(login_attempt_id) = &login.login_attempt_id {
+        let two_fa_code_store = &app_state.two_fa_code_store;
+        if let Err(err) = check_login_attempt(two_fa_code_store, &login.email, login_attempt_id).await {
+            return (jar, Err(err));
+        }
+        if two_fa_code_store
+            .write()
+            .await
+            .remove_code(&login.email)
+            .await
+            .is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    match add_login_cookies(jar.clone(), &app_state, &login.email, client).await {
+        Ok(jar) => (jar, Ok(http::StatusCode::OK)),
+        Err(err) => (jar, Err(err)),
+    }
+}
+
+// The password step of the login must still be pending for the passkey to complete it
+async fn check_login_attempt(
+    two_fa_code_store: &TwoFACodeStoreType,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<(), AuthAPIError> {
+    let entry = match two_fa_code_store.read().await.get_code(email).await {
+        Ok(entry) => entry,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if &entry.login_attempt_id != login_attempt_id {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    if entry.is_expired() {
+        return Err(AuthAPIError::TwoFACodeExpired);
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct PasskeyLoginStartParams {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyLoginChallenge {
+    // Sent back with the authenticator's answer to `/passkey/login/finish`
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    // The options to pass to `navigator.credentials.get()`
+    #[serde(flatten)]
+    pub challenge: RequestChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct PasskeyLoginFinishParams {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    pub credential: PublicKeyCredential,
+}
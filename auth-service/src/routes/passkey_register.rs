@@ -0,0 +1,77 @@
+use crate::domain::data_stores::CredentialStoreError;
+use crate::domain::errors::AuthAPIError;
+use crate::utils::auth::validate_auth_cookie;
+use crate::utils::webauthn::{user_unique_id, WEBAUTHN};
+use crate::AppState;
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use webauthn_rs::prelude::{CreationChallengeResponse, RegisterPublicKeyCredential};
+
+// Ask the browser to create a passkey for the logged-in user
+pub async fn start_passkey_registration(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> Result<Json<CreationChallengeResponse>, AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let mut credential_store = app_state.credential_store.write().await;
+
+    // Stop the authenticator from creating a second passkey for the same account
+    let existing = credential_store
+        .get_credentials(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .iter()
+        .map(|passkey| passkey.cred_id().clone())
+        .collect();
+
+    let (challenge, state) = WEBAUTHN
+        .start_passkey_registration(
+            user_unique_id(&user.id),
+            email.as_ref(),
+            email.as_ref(),
+            Some(existing),
+        )
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    credential_store
+        .add_registration(email, state)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(challenge))
+}
+
+// Check the authenticator's answer and keep the new passkey
+pub async fn finish_passkey_registration(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Json(credential): Json<RegisterPublicKeyCredential>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let mut credential_store = app_state.credential_store.write().await;
+
+    let ceremony = match credential_store.take_registration(&email).await {
+        Ok(ceremony) if !ceremony.is_expired() => ceremony,
+        Ok(_) | Err(CredentialStoreError::CeremonyNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let passkey = WEBAUTHN
+        .finish_passkey_registration(&credential, &ceremony.state)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    match credential_store.add_credential(&email, passkey).await {
+        Ok(()) => Ok(http::StatusCode::CREATED),
+        Err(CredentialStoreError::CredentialAlreadyExists) => {
+            Err(AuthAPIError::PasskeyAlreadyRegistered)
+        }
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
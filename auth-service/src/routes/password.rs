@@ -0,0 +1,194 @@
+use crate::domain::data_stores::UserStoreError;
+use crate::domain::errors::AuthAPIError;
+use crate::domain::{Email, Password};
+use crate::utils::auth::{
+    consume_email_token, generate_password_reset_token, revoke_all_sessions, revoke_session,
+    validate_auth_cookie, validate_password_reset_token,
+};
+use crate::utils::constants::{JWT_COOKIE_NAME, PASSWORD_RESET_URL, REFRESH_COOKIE_NAME};
+use crate::{AppState, EmailClientType};
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+// Email the user a link to set a new password. The response is the same whether or not
+// the account exists, and the email goes out in the background so that the time taken
+// does not tell either.
+pub async fn forgot_password(
+    State(app_state): State<AppState>,
+    Json(request): Json<ForgotPasswordRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match app_state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok(http::StatusCode::OK),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token = generate_password_reset_token(&email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    tokio::spawn(send_password_reset_email(
+        app_state.email_client.clone(),
+        email,
+        token,
+    ));
+
+    Ok(http::StatusCode::OK)
+}
+
+async fn send_password_reset_email(client: EmailClientType, email: Email, token: String) {
+    let subject = "Reset your password";
+    let body = format!(
+        "Follow this link to choose a new password: {}?token={}\n\
+         If you did not ask to reset your password, you can ignore this email.",
+        *PASSWORD_RESET_URL, token
+    );
+
+    if let Err(err) = client.send_email(&email, subject, &body).await {
+        eprintln!("Failed to send password reset email: {}", err);
+    }
+}
+
+// Set a new password with the token from the reset link. The token cannot be used again,
+// and every session of the user ends, as whoever knew the old password may be logged in.
+pub async fn reset_password(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ResetPasswordRequest>,
+) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
+    let Ok(password) = Password::parse(&request.new_password) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+    let claims = match validate_password_reset_token(&request.token, &app_state).await {
+        Ok(claims) => claims,
+        Err(err) => return (jar, Err(err)),
+    };
+    let Ok(email) = Email::parse(&claims.sub) else {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    };
+
+    // Used up before the password is touched: only the one request that uses it up may set it
+    if let Err(err) = consume_email_token(claims, &app_state.banned_token_store).await {
+        return (jar, Err(err));
+    }
+
+    match app_state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, &password)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    if let Err(err) = revoke_all_sessions(&email, &app_state).await {
+        return (jar, Err(err));
+    }
+
+    // This browser may still hold one of the sessions just ended
+    let jar = jar
+        .remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+        .remove(Cookie::build(REFRESH_COOKIE_NAME).path("/"));
+
+    (jar, Ok(http::StatusCode::OK))
+}
+
+// Change the logged-in user's password, which takes the current one. The user may
+// also log out every other device, in case someone else knows the old password.
+pub async fn change_password(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let (claims, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let new_password =
+        Password::parse(&request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    // A current password that breaks the policy cannot be the right one
+    let current_password = Password::parse(&request.current_password)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let validation = app_state
+        .user_store
+        .read()
+        .await
+        .validate_user(&email, &current_password)
+        .await;
+    match validation {
+        Ok(_) => {}
+        Err(UserStoreError::IncorrectCredentials) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    app_state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, &new_password)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if request.logout_other_sessions {
+        revoke_other_sessions(&email, &claims.sid, &app_state).await?;
+    }
+
+    Ok(http::StatusCode::OK)
+}
+
+// End every session of the user but `current`, which stays logged in
+async fn revoke_other_sessions(
+    email: &Email,
+    current: &str,
+    app_state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let sessions = app_state
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    for session in sessions
+        .iter()
+        .filter(|session| session.id.as_ref() != current)
+    {
+        match revoke_session(email, &session.id, app_state).await {
+            // Ended by other means in the meantime
+            Ok(()) | Err(AuthAPIError::SessionNotFound) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+    #[serde(rename = "logoutOtherSessions", default)]
+    pub logout_other_sessions: bool,
+}
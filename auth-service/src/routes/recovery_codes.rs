@@ -0,0 +1,56 @@
+use crate::domain::data_stores::{UserStore, UserStoreError};
+use crate::domain::errors::AuthAPIError;
+use crate::domain::{Email, RecoveryCode};
+use crate::utils::auth::validate_auth_cookie;
+use crate::AppState;
+use axum::extract::State;
+use axum::{http, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+// Replace the logged-in user's recovery codes with a fresh set; the old ones stop working
+pub async fn regenerate_recovery_codes(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(http::StatusCode, Json<RecoveryCodesResponse>), AuthAPIError> {
+    let (_, user) = validate_auth_cookie(&jar, &app_state).await?;
+    let email = user.email;
+
+    let mut user_store = app_state.user_store.write().await;
+
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let recovery_codes = issue_recovery_codes(&mut *user_store, &email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((
+        http::StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+// Generates and stores a new set, returning the codes as they should be shown to the user.
+// This is the only time the plaintext codes are available.
+pub(crate) async fn issue_recovery_codes(
+    user_store: &mut (dyn UserStore + Send + Sync),
+    email: &Email,
+) -> Result<Vec<String>, UserStoreError> {
+    let codes = RecoveryCode::generate_set();
+    user_store.set_recovery_codes(email, &codes).await?;
+
+    Ok(codes.iter().map(RecoveryCode::to_string).collect())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,139 @@
+use crate::domain::data_stores::{RefreshTokenStoreError, SessionStoreError, UserStoreError};
+use crate::domain::errors::AuthAPIError;
+use crate::domain::RefreshToken;
+use crate::utils::auth::{generate_auth_cookie, generate_refresh_cookie};
+use crate::utils::constants::REFRESH_COOKIE_NAME;
+use crate::AppState;
+use axum::extract::State;
+use axum::http;
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+
+// Trade the refresh token cookie for a new JWT and rotate the refresh token.
+// A refresh token that was already rotated is being replayed, by whoever stole it
+// or by its owner after the thief got there first, so its whole family is revoked.
+pub async fn refresh(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
+    let Some(cookie) = jar.get(REFRESH_COOKIE_NAME) else {
+        return (jar, Err(AuthAPIError::MissingToken));
+    };
+    let cookie = cookie.to_owned();
+
+    let Ok(token) = RefreshToken::parse(cookie.value().to_owned()) else {
+        return (jar.remove(cookie), Err(AuthAPIError::InvalidToken));
+    };
+
+    // Using the token up is what settles which request gets to rotate it, so the store
+    // is only locked for each step rather than across the session and user lookups
+    let used = app_state
+        .refresh_token_store
+        .write()
+        .await
+        .use_token(&token)
+        .await;
+    let entry = match used {
+        Ok(entry) => entry,
+        Err(RefreshTokenStoreError::TokenNotFound) => {
+            return (jar.remove(cookie), Err(AuthAPIError::InvalidToken))
+        }
+        Err(RefreshTokenStoreError::UnexpectedError) => {
+            return (jar, Err(AuthAPIError::UnexpectedError))
+        }
+    };
+
+    if entry.used {
+        if app_state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_family(&entry.family_id)
+            .await
+            .is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+        return (jar.remove(cookie), Err(AuthAPIError::InvalidToken));
+    }
+
+    if entry.is_expired() {
+        return (jar.remove(cookie), Err(AuthAPIError::InvalidToken));
+    }
+
+    // The session knows the user's current address, which may have changed since the
+    // family started. Ending the session from another device ends the family too.
+    let session = match app_state
+        .session_store
+        .read()
+        .await
+        .get_session(&entry.family_id)
+        .await
+    {
+        Ok(session) => Some(session),
+        Err(SessionStoreError::SessionNotFound) => None,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+    // A logout-all since this token was issued ends its family, as it does every access token
+    let user = match &session {
+        Some(session) => match app_state
+            .user_store
+            .read()
+            .await
+            .get_user(&session.email)
+            .await
+        {
+            Ok(user) => Some(user),
+            Err(UserStoreError::UserNotFound) => None,
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        },
+        None => None,
+    };
+    let accepted = user
+        .as_ref()
+        .is_some_and(|user| user.accepts_token_issued_at(entry.issued_at));
+    // Otherwise the session was just seen
+    let accepted = accepted
+        && match app_state
+            .session_store
+            .write()
+            .await
+            .touch_session(&entry.family_id, Utc::now().timestamp())
+            .await
+        {
+            Ok(()) => true,
+            Err(SessionStoreError::SessionNotFound) => false,
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        };
+    let user = match user {
+        Some(user) if accepted => user,
+        _ => {
+            if app_state
+                .refresh_token_store
+                .write()
+                .await
+                .revoke_family(&entry.family_id)
+                .await
+                .is_err()
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError));
+            }
+            return (jar.remove(cookie), Err(AuthAPIError::InvalidToken));
+        }
+    };
+
+    let Ok(auth_cookie) = generate_auth_cookie(&user.id, &entry.family_id) else {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    };
+    let mut refresh_token_store = app_state.refresh_token_store.write().await;
+    let Ok(refresh_cookie) =
+        generate_refresh_cookie(&mut *refresh_token_store, &user.email, entry.family_id).await
+    else {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    };
+
+    (
+        jar.add(auth_cookie).add(refresh_cookie),
+        Ok(http::StatusCode::OK),
+    )
+}
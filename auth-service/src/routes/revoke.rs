@@ -0,0 +1,76 @@
+use crate::domain::errors::AuthAPIError;
+use crate::domain::RefreshToken;
+use crate::utils::auth::{
+    revoke_access_token, revoke_refresh_token, validate_admin_token, validate_client_credentials,
+    validate_token,
+};
+use crate::utils::constants::{ADMIN_TOKEN, CLIENT_CREDENTIALS};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Form;
+use serde::Deserialize;
+
+// RFC 7009 token revocation, for trusted services and admins to kill a leaked token.
+// Access tokens are banned just like `/logout` bans them; a refresh token takes every
+// token rotated from the same login with it. As the RFC asks, a token that is unknown,
+// invalid or already expired is answered with a 200 all the same.
+pub async fn revoke(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Form(params): Form<RevokeParams>,
+) -> Result<StatusCode, AuthAPIError> {
+    validate_revoker(&headers)?;
+
+    // The hint only decides which kind of token is looked for first
+    if params.token_type_hint.as_deref() == Some("refresh_token") {
+        if !revoke_as_refresh_token(&app_state, &params.token).await? {
+            revoke_as_access_token(&app_state, &params.token).await?;
+        }
+    } else if !revoke_as_access_token(&app_state, &params.token).await? {
+        revoke_as_refresh_token(&app_state, &params.token).await?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+// Admins send their `Bearer` admin token, trusted services their client credentials
+fn validate_revoker(headers: &HeaderMap) -> Result<(), AuthAPIError> {
+    let is_bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "));
+
+    if is_bearer {
+        validate_admin_token(headers, ADMIN_TOKEN.as_deref())
+    } else {
+        validate_client_credentials(headers, &CLIENT_CREDENTIALS)
+    }
+}
+
+async fn revoke_as_access_token(app_state: &AppState, token: &str) -> Result<bool, AuthAPIError> {
+    let claims = match validate_token(token, &app_state.user_store).await {
+        Ok((claims, _)) => claims,
+        Err(AuthAPIError::InvalidToken) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    revoke_access_token(claims, &app_state.banned_token_store).await?;
+
+    Ok(true)
+}
+
+async fn revoke_as_refresh_token(app_state: &AppState, token: &str) -> Result<bool, AuthAPIError> {
+    let Ok(token) = RefreshToken::parse(token.to_owned()) else {
+        return Ok(false);
+    };
+
+    revoke_refresh_token(&token, &app_state.refresh_token_store).await
+}
+
+#[derive(Deserialize)]
+pub struct RevokeParams {
+    pub token: String,
+    // "access_token" or "refresh_token"; anything else is ignored
+    pub token_type_hint: Option<String>,
+}
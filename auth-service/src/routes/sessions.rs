@@ -0,0 +1,97 @@
+use crate::domain::data_stores::{Session, SessionId};
+use crate::domain::errors::AuthAPIError;
+use crate::utils::auth::{revoke_session, validate_auth_cookie};
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::{http, Json};
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+// Every device the user is logged in on, most recently seen first
+pub async fn list_sessions(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> Result<Json<SessionsResponse>, AuthAPIError> {
+    let (claims, user) = validate_auth_cookie(&jar, &app_state).await?;
+
+    let mut sessions = app_state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+
+    Ok(Json(SessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, &claims.sid))
+            .collect(),
+    }))
+}
+
+// Log one of the user's devices out
+pub async fn delete_session(
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
+    let (claims, user) = match validate_auth_cookie(&jar, &app_state).await {
+        Ok(validated) => validated,
+        Err(err) => return (jar, Err(err)),
+    };
+    let Ok(session_id) = SessionId::parse(id) else {
+        return (jar, Err(AuthAPIError::SessionNotFound));
+    };
+
+    // Someone else's session is reported as missing, rather than as forbidden
+    if let Err(err) = revoke_session(&user.email, &session_id, &app_state).await {
+        return (jar, Err(err));
+    }
+
+    // Ending the current session is a logout
+    let jar = if session_id.as_ref() == claims.sid {
+        jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+            .remove(Cookie::build(REFRESH_COOKIE_NAME).path("/"))
+    } else {
+        jar
+    };
+
+    (jar, Ok(http::StatusCode::OK))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    // Seconds since the epoch
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: i64,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    // Whether this is the session the request was made from
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: &str) -> Self {
+        Self {
+            current: session.id.as_ref() == current_session_id,
+            id: session.id.as_ref().to_owned(),
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            ip: session.ip,
+            user_agent: session.user_agent,
+        }
+    }
+}
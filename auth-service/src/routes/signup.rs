@@ -1,7 +1,9 @@
 use crate::domain::data_stores::UserStoreError;
 use crate::domain::errors::AuthAPIError;
-use crate::domain::user::User;
+use crate::domain::user::NewUser;
 use crate::domain::{Email, Password};
+use crate::routes::recovery_codes::issue_recovery_codes;
+use crate::routes::verify_email::send_verification_email;
 use crate::{AppState};
 use axum::{extract::State, http, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
@@ -10,7 +12,7 @@ pub async fn signup(
     State(state): State<AppState>,
     Json(params): Json<SignUpParams>,
 ) -> impl IntoResponse {
-    let user: User;
+    let user: NewUser;
 
     match params.to_user() {
         Some(u) => user = u,
@@ -21,16 +23,30 @@ pub async fn signup(
 
 
     let mut user_store = state.user_store.write().await;
+    let (email, requires_2fa) = (user.email.clone(), user.requires_2fa);
 
     match user_store.add_user(user).await {
-        Err(UserStoreError::UserAlreadyExists) => AuthAPIError::UserAlreadyExists.into_response(),
-        Err(_) => AuthAPIError::UnexpectedError.into_response(),
-        Ok(_) => (
-            http::StatusCode::CREATED,
-            Json(SignUpResponse::new("User created successfully!")),
-        )
-            .into_response(),
+        Err(UserStoreError::UserAlreadyExists) => return AuthAPIError::UserAlreadyExists.into_response(),
+        Err(_) => return AuthAPIError::UnexpectedError.into_response(),
+        Ok(_) => {}
     }
+
+    // New accounts start out unverified, until the user follows the emailed link
+    if let Err(err) = send_verification_email(&mut *user_store, state.email_client.clone(), &email).await {
+        return err.into_response();
+    }
+
+    let mut response = SignUpResponse::new("User created successfully!");
+
+    // Users who sign up with 2FA get their recovery codes straight away
+    if requires_2fa {
+        match issue_recovery_codes(&mut *user_store, &email).await {
+            Ok(codes) => response.recovery_codes = Some(codes),
+            Err(_) => return AuthAPIError::UnexpectedError.into_response(),
+        }
+    }
+
+    (http::StatusCode::CREATED, Json(response)).into_response()
 }
 
 #[derive(Deserialize)]
@@ -42,10 +58,10 @@ pub struct SignUpParams {
 }
 
 impl SignUpParams {
-    fn to_user(&self) -> Option<User> {
+    fn to_user(&self) -> Option<NewUser> {
         match (Email::parse(&self.email), Password::parse(&self.password)) {
             (Ok(email), Ok(password)) => {
-                Some(User {
+                Some(NewUser {
                     email,
                     password,
                     requires_2fa: self.requires_2fa,
@@ -59,12 +75,15 @@ impl SignUpParams {
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct SignUpResponse {
     pub message: String,
+    #[serde(rename = "recoveryCodes", default, skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 impl SignUpResponse {
     fn new(message: &str) -> SignUpResponse {
         SignUpResponse {
             message: message.to_string(),
+            recovery_codes: None,
         }
     }
 }
@@ -1,59 +1,155 @@
-use crate::domain::data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::data_stores::{
+    LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, UserStoreError,
+};
 use crate::domain::errors::AuthAPIError;
 use crate::domain::errors::AuthAPIError::UnexpectedError;
-use crate::domain::Email;
-use crate::utils::auth::generate_auth_cookie;
-use crate::AppState;
+use crate::domain::user::{TwoFAMethod, User};
+use crate::domain::{Email, RecoveryCode};
+use crate::utils::auth::add_login_cookies;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::TWO_FA_MAX_ATTEMPTS;
+use crate::{AppState, UserStoreType};
 use axum::extract::State;
 use axum::{http, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use serde::Deserialize;
 use tokio::sync::RwLockWriteGuard;
 
 pub async fn verify_2fa(
     State(app_state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(params): Json<Verify2FAParams>,
 ) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
     let (Ok(email), Ok(login_attempt_id), Ok(code)) = (
         params.parse_email(),
         params.parse_login_attempt(),
-        params.parse_two_fa_code(),
+        params.parse_second_factor(),
     ) else {
         return (jar, Err(AuthAPIError::InvalidCredentials));
     };
 
-    let store = app_state.two_fa_code_store.write().await;
+    let mut store = app_state.two_fa_code_store.write().await;
 
-    match store.get_code(&email).await {
-        Err(TwoFACodeStoreError::UnexpectedError) => (jar, Err(AuthAPIError::UnexpectedError)),
+    let entry = match store.get_code(&email).await {
+        Err(TwoFACodeStoreError::UnexpectedError) => return (jar, Err(AuthAPIError::UnexpectedError)),
         Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
-            (jar, Err(AuthAPIError::IncorrectCredentials))
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
         }
-        Ok((stored_login_attempt_id, stored_two_fa_code)) => {
-            if login_attempt_id == stored_login_attempt_id && stored_two_fa_code == code {
-                setup_auth(email, jar, store).await
-            } else {
-                (jar, Err(AuthAPIError::IncorrectCredentials))
-            }
+        Ok(entry) => entry,
+    };
+
+    // Only guesses against the caller's own login attempt count towards the limit,
+    // so someone who merely knows the email cannot burn another user's attempt
+    if entry.login_attempt_id != login_attempt_id {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    if entry.is_expired() {
+        if store.remove_code(&email).await.is_err() {
+            return (jar, Err(UnexpectedError));
         }
+        return (jar, Err(AuthAPIError::TwoFACodeExpired));
     }
+
+    let code_matches = match code {
+        SecondFactor::Code(code) => {
+            check_code(&app_state.user_store, &email, &entry.code, &code).await
+        }
+        SecondFactor::RecoveryCode(code) => {
+            check_recovery_code(&app_state.user_store, &email, &code).await
+        }
+    };
+    let code_matches = match code_matches {
+        Ok(matches) => matches,
+        Err(err) => return (jar, Err(err)),
+    };
+
+    if !code_matches {
+        return match store.record_failed_attempt(&email).await {
+            Ok(attempts) if attempts >= *TWO_FA_MAX_ATTEMPTS => {
+                if store.remove_code(&email).await.is_err() {
+                    return (jar, Err(UnexpectedError));
+                }
+                (jar, Err(AuthAPIError::TooManyTwoFAAttempts))
+            }
+            Ok(_) => (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(_) => (jar, Err(UnexpectedError)),
+        };
+    }
+
+    setup_auth(email, jar, store, &app_state, client).await
+}
+
+// Users who set up an authenticator app answer with a TOTP code; everyone else with the emailed code
+async fn check_code(
+    user_store: &UserStoreType,
+    email: &Email,
+    sent_code: &TwoFACode,
+    code: &TwoFACode,
+) -> Result<bool, AuthAPIError> {
+    let user = match user_store.read().await.get_user(email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(sent_code == code),
+        Err(_) => return Err(UnexpectedError),
+    };
+
+    match user.two_fa_method {
+        TwoFAMethod::Email => Ok(sent_code == code),
+        TwoFAMethod::Totp => verify_totp(user_store, &user, code).await,
+    }
+}
+
+// A recovery code is used up as soon as it is accepted
+async fn check_recovery_code(
+    user_store: &UserStoreType,
+    email: &Email,
+    code: &RecoveryCode,
+) -> Result<bool, AuthAPIError> {
+    match user_store.write().await.use_recovery_code(email, code).await {
+        Ok(used) => Ok(used),
+        Err(UserStoreError::UserNotFound) => Ok(false),
+        Err(_) => Err(UnexpectedError),
+    }
+}
+
+// A replayed code counts as a wrong guess, just like a code that never matched
+async fn verify_totp(
+    user_store: &UserStoreType,
+    user: &User,
+    code: &TwoFACode,
+) -> Result<bool, AuthAPIError> {
+    let Some(secret) = &user.totp_secret else {
+        return Err(UnexpectedError);
+    };
+    let Some(step) = secret.verify(code.as_ref(), Utc::now().timestamp() as u64) else {
+        return Ok(false);
+    };
+
+    user_store
+        .write()
+        .await
+        .use_totp_step(&user.email, step)
+        .await
+        .map_err(|_| UnexpectedError)
 }
 
 async fn setup_auth(
     email: Email,
     jar: CookieJar,
     mut two_fa_code_store: RwLockWriteGuard<'_, dyn TwoFACodeStore + Send + Sync>,
+    app_state: &AppState,
+    client: ClientInfo,
 ) -> (CookieJar, Result<http::StatusCode, AuthAPIError>) {
-    let Ok(auth_cookie) = generate_auth_cookie(&email) else {
-        return (jar, Err(UnexpectedError));
-    };
-
     if two_fa_code_store.remove_code(&email).await.is_err() {
         return (jar, Err(UnexpectedError));
     }
 
-    (jar.add(auth_cookie), Ok(http::StatusCode::OK))
+    match add_login_cookies(jar.clone(), app_state, &email, client).await {
+        Ok(jar) => (jar, Ok(http::StatusCode::OK)),
+        Err(err) => (jar, Err(err)),
+    }
 }
 
 #[derive(Deserialize)]
@@ -76,7 +172,16 @@ impl Verify2FAParams {
         LoginAttemptId::parse(self.login_attempt_id.clone())
     }
 
-    fn parse_two_fa_code(&self) -> Result<TwoFACode, String> {
+    // Anything that is not a 2FA code may still be one of the user's recovery codes
+    fn parse_second_factor(&self) -> Result<SecondFactor, String> {
         TwoFACode::parse(self.code.clone())
+            .map(SecondFactor::Code)
+            .or_else(|_| RecoveryCode::parse(&self.code).map(SecondFactor::RecoveryCode))
     }
 }
+
+// What the user answered the 2FA challenge with
+enum SecondFactor {
+    Code(TwoFACode),
+    RecoveryCode(RecoveryCode),
+}
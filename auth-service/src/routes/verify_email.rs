@@ -0,0 +1,120 @@
+use crate::domain::data_stores::UserStore;
+use crate::domain::errors::AuthAPIError;
+use crate::domain::Email;
+use crate::utils::auth::{
+    consume_email_token, generate_email_token, validate_email_token, EmailTokenPurpose,
+};
+use crate::utils::constants::{EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS, EMAIL_VERIFICATION_URL};
+use crate::{AppState, EmailClientType};
+use axum::extract::State;
+use axum::{http, Json};
+use chrono::Utc;
+use serde::Deserialize;
+
+// Confirm the user's address with the token from the verification link
+pub async fn verify_email(
+    State(app_state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let (claims, user) = validate_email_token(
+        &request.token,
+        EmailTokenPurpose::EmailVerification,
+        &app_state,
+    )
+    .await?;
+    consume_email_token(claims, &app_state.banned_token_store).await?;
+
+    if !user.email_verified {
+        app_state
+            .user_store
+            .write()
+            .await
+            .mark_email_verified(&user.email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(http::StatusCode::OK)
+}
+
+// Send the verification link again. As with `/password/forgot`, the response does not tell
+// whether the account exists, nor whether it is verified already. Requests within
+// EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS of the last email are dropped just as quietly.
+pub async fn resend_verification_email(
+    State(app_state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<http::StatusCode, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Recording the send is the throttle itself: the store only records it when an email is
+    // due, so of concurrent requests only one gets to send
+    let now = Utc::now().timestamp();
+    let due = app_state
+        .user_store
+        .write()
+        .await
+        .try_set_verification_sent_at(
+            &email,
+            now,
+            now - *EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS as i64,
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if due {
+        queue_verification_email(app_state.email_client.clone(), &email)?;
+    }
+
+    Ok(http::StatusCode::OK)
+}
+
+// Email the user a link to verify their address, and remember when, for the throttle
+pub(crate) async fn send_verification_email(
+    user_store: &mut (dyn UserStore + Send + Sync),
+    email_client: EmailClientType,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    user_store
+        .set_verification_sent_at(email, Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    queue_verification_email(email_client, email)
+}
+
+// The email goes out in the background, so the time taken tells nothing either
+fn queue_verification_email(
+    email_client: EmailClientType,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    let token = generate_email_token(email, EmailTokenPurpose::EmailVerification)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    tokio::spawn(deliver_verification_email(
+        email_client,
+        email.clone(),
+        token,
+    ));
+
+    Ok(())
+}
+
+async fn deliver_verification_email(client: EmailClientType, email: Email, token: String) {
+    let subject = "Verify your email address";
+    let body = format!(
+        "Follow this link to verify your email address: {}?token={}",
+        *EMAIL_VERIFICATION_URL, token
+    );
+
+    if let Err(err) = client.send_email(&email, subject, &body).await {
+        eprintln!("Failed to send verification email: {}", err);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
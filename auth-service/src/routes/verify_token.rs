@@ -3,16 +3,13 @@ use axum::extract::State;
 use serde::Deserialize;
 use crate::{AppState};
 use crate::domain::errors::AuthAPIError;
-use crate::utils::auth::validate_token;
+use crate::utils::auth::validate_unbanned_token;
 
 pub async fn verify_token(
     State(app_state): State<AppState>,
     Json(params): Json<VerifyTokenParams>,
 ) -> Result<http::StatusCode, AuthAPIError> {
-    validate_token(&params.token).await.map_err(|_| AuthAPIError::InvalidToken)?;
-    if app_state.banned_token_store.read().await.contains(&params.token).await {
-        return Err(AuthAPIError::InvalidToken)
-    }
+    validate_unbanned_token(&params.token, &app_state).await?;
 
     Ok(http::StatusCode::OK)
 }
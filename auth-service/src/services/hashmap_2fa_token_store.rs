@@ -1,10 +1,12 @@
-use crate::domain::data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::data_stores::{
+    LoginAttemptId, TwoFACode, TwoFACodeEntry, TwoFACodeStore, TwoFACodeStoreError,
+};
 use crate::domain::Email;
 use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct HashMap2FaTokenStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<Email, TwoFACodeEntry>,
 }
 
 impl HashMap2FaTokenStore {
@@ -21,7 +23,8 @@ impl TwoFACodeStore for HashMap2FaTokenStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes
+            .insert(email, TwoFACodeEntry::new(login_attempt_id, code));
 
         Ok(())
     }
@@ -32,15 +35,41 @@ impl TwoFACodeStore for HashMap2FaTokenStore {
         Ok(())
     }
 
-    async fn get_code(
-        &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+    async fn get_code(&self, email: &Email) -> Result<TwoFACodeEntry, TwoFACodeStoreError> {
         self.codes
             .get(email)
-            .and_then(|v| Some(v.clone()))
+            .cloned()
             .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
     }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let entry = self
+            .codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        entry.failed_attempts += 1;
+
+        Ok(entry.failed_attempts)
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), TwoFACodeStoreError> {
+        if let Some(entry) = self.codes.remove(email) {
+            self.codes.insert(new_email.clone(), entry);
+        }
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, TwoFACodeStoreError> {
+        let before = self.codes.len();
+        self.codes.retain(|_, entry| !entry.is_expired());
+
+        Ok((before - self.codes.len()) as u64)
+    }
 }
 
 #[cfg(test)]
@@ -60,7 +89,7 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(store.codes.contains_key(&email), true);
+        assert!(store.codes.contains_key(&email));
     }
 
     #[tokio::test]
@@ -72,12 +101,12 @@ mod tests {
             .codes
             .insert(
                 email.clone(),
-                (LoginAttemptId::default(), TwoFACode::default()),
+                TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default()),
             ).is_none());
 
         assert!(store.remove_code(&email).await.is_ok());
 
-        assert_eq!(store.codes.contains_key(&email), false);
+        assert!(!store.codes.contains_key(&email));
     }
 
     #[tokio::test]
@@ -96,16 +125,91 @@ mod tests {
         let email = Email::parse("user@example.com").unwrap();
         let mut store = HashMap2FaTokenStore::default();
 
-        let login_attempt = LoginAttemptId::default();
-        let two_fa_code = TwoFACode::default();
+        let entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
 
         assert!(store
             .codes
-            .insert(
-                email.clone(),
-                (login_attempt.clone(), two_fa_code.clone()),
-            ).is_none());
+            .insert(email.clone(), entry.clone())
+            .is_none());
+
+        assert_eq!(store.get_code(&email).await, Ok(entry));
+    }
 
-        assert_eq!(store.get_code(&email).await, Ok((login_attempt, two_fa_code)));
+    #[tokio::test]
+    async fn test_get_code_returns_expired_entry() {
+        let email = Email::parse("user@example.com").unwrap();
+        let mut store = HashMap2FaTokenStore::default();
+
+        let mut entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
+        entry.issued_at -= entry.ttl_seconds as i64;
+        store.codes.insert(email.clone(), entry);
+
+        assert!(store.get_code(&email).await.unwrap().is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let email = Email::parse("user@example.com").unwrap();
+        let mut store = HashMap2FaTokenStore::default();
+
+        assert_eq!(
+            store.record_failed_attempt(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+        assert_eq!(store.get_code(&email).await.unwrap().failed_attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let (expired, live) = (
+            Email::parse("expired@example.com").unwrap(),
+            Email::parse("live@example.com").unwrap(),
+        );
+        let mut store = HashMap2FaTokenStore::default();
+
+        let mut entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
+        entry.issued_at -= entry.ttl_seconds as i64;
+        store.codes.insert(expired.clone(), entry);
+        store
+            .add_code(live.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.purge_expired().await, Ok(1));
+        assert!(!store.codes.contains_key(&expired));
+        assert!(store.codes.contains_key(&live));
+    }
+
+    #[tokio::test]
+    async fn test_change_email_keeps_failed_attempts() {
+        let email = Email::parse("user@example.com").unwrap();
+        let new_email = Email::parse("new@example.com").unwrap();
+        let mut store = HashMap2FaTokenStore::default();
+
+        // Nothing pending, nothing to move
+        assert_eq!(store.change_email(&email, &new_email).await, Ok(()));
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+        store.record_failed_attempt(&email).await.unwrap();
+        let entry = store.get_code(&email).await.unwrap();
+
+        store.change_email(&email, &new_email).await.unwrap();
+
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(store.get_code(&new_email).await, Ok(entry));
     }
 }
@@ -0,0 +1,294 @@
+use crate::domain::data_stores::{
+    CredentialStore, CredentialStoreError, PasskeyCeremony, PasskeyCeremonyId, PasskeyLogin,
+};
+use crate::domain::Email;
+use std::collections::HashMap;
+use webauthn_rs::prelude::{AuthenticationResult, Passkey, PasskeyRegistration};
+
+#[derive(Default)]
+pub struct HashMapCredentialStore {
+    credentials: HashMap<Email, Vec<Passkey>>,
+    registrations: HashMap<Email, PasskeyCeremony<PasskeyRegistration>>,
+    logins: HashMap<PasskeyCeremonyId, PasskeyCeremony<PasskeyLogin>>,
+}
+
+impl HashMapCredentialStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialStore for HashMapCredentialStore {
+    async fn add_credential(
+        &mut self,
+        email: &Email,
+        passkey: Passkey,
+    ) -> Result<(), CredentialStoreError> {
+        if self
+            .credentials
+            .values()
+            .flatten()
+            .any(|existing| existing.cred_id() == passkey.cred_id())
+        {
+            return Err(CredentialStoreError::CredentialAlreadyExists);
+        }
+
+        self.credentials
+            .entry(email.clone())
+            .or_default()
+            .push(passkey);
+
+        Ok(())
+    }
+
+    async fn get_credentials(&self, email: &Email) -> Result<Vec<Passkey>, CredentialStoreError> {
+        Ok(self.credentials.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn update_credential(
+        &mut self,
+        email: &Email,
+        result: &AuthenticationResult,
+    ) -> Result<(), CredentialStoreError> {
+        let passkey = self
+            .credentials
+            .get_mut(email)
+            .into_iter()
+            .flatten()
+            .find(|passkey| passkey.cred_id() == result.cred_id())
+            .ok_or(CredentialStoreError::CredentialNotFound)?;
+        passkey.update_credential(result);
+
+        Ok(())
+    }
+
+    async fn add_registration(
+        &mut self,
+        email: Email,
+        state: PasskeyRegistration,
+    ) -> Result<(), CredentialStoreError> {
+        self.registrations.insert(email, PasskeyCeremony::new(state));
+
+        Ok(())
+    }
+
+    async fn take_registration(
+        &mut self,
+        email: &Email,
+    ) -> Result<PasskeyCeremony<PasskeyRegistration>, CredentialStoreError> {
+        self.registrations
+            .remove(email)
+            .ok_or(CredentialStoreError::CeremonyNotFound)
+    }
+
+    async fn add_login(
+        &mut self,
+        ceremony_id: PasskeyCeremonyId,
+        login: PasskeyLogin,
+    ) -> Result<(), CredentialStoreError> {
+        self.logins.insert(ceremony_id, PasskeyCeremony::new(login));
+
+        Ok(())
+    }
+
+    async fn take_login(
+        &mut self,
+        ceremony_id: &PasskeyCeremonyId,
+    ) -> Result<PasskeyCeremony<PasskeyLogin>, CredentialStoreError> {
+        self.logins
+            .remove(ceremony_id)
+            .ok_or(CredentialStoreError::CeremonyNotFound)
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, CredentialStoreError> {
+        let before = self.registrations.len() + self.logins.len();
+        self.registrations.retain(|_, ceremony| !ceremony.is_expired());
+        self.logins.retain(|_, ceremony| !ceremony.is_expired());
+
+        Ok((before - self.registrations.len() - self.logins.len()) as u64)
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), CredentialStoreError> {
+        if let Some(passkeys) = self.credentials.remove(email) {
+            self.credentials.insert(new_email.clone(), passkeys);
+        }
+        if let Some(registration) = self.registrations.remove(email) {
+            self.registrations.insert(new_email.clone(), registration);
+        }
+        for ceremony in self.logins.values_mut() {
+            if &ceremony.state.email == email {
+                ceremony.state.email = new_email.clone();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::user::UserId;
+    use crate::utils::constants::{PASSKEY_CEREMONY_TTL_SECONDS, WEBAUTHN_RP_ORIGIN};
+    use crate::utils::webauthn::{user_unique_id, WEBAUTHN};
+    use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+    use webauthn_authenticator_rs::WebauthnAuthenticator;
+    use webauthn_rs::prelude::Url;
+
+    // Registers a passkey with a software authenticator, as a browser would
+    fn register_passkey(email: &Email) -> Passkey {
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        let (challenge, state) = WEBAUTHN
+            .start_passkey_registration(
+                user_unique_id(&UserId::default()),
+                email.as_ref(),
+                email.as_ref(),
+                None,
+            )
+            .unwrap();
+        let response = authenticator
+            .do_registration(Url::parse(&WEBAUTHN_RP_ORIGIN).unwrap(), challenge)
+            .unwrap();
+
+        WEBAUTHN
+            .finish_passkey_registration(&response, &state)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credentials() {
+        let mut store = HashMapCredentialStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert_eq!(store.get_credentials(&email).await.unwrap().len(), 0);
+
+        let passkey = register_passkey(&email);
+        store.add_credential(&email, passkey.clone()).await.unwrap();
+
+        let credentials = store.get_credentials(&email).await.unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].cred_id(), passkey.cred_id());
+    }
+
+    #[tokio::test]
+    async fn test_credential_ids_are_unique_across_users() {
+        let mut store = HashMapCredentialStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let other = Email::parse("other@example.com").unwrap();
+        let passkey = register_passkey(&email);
+
+        store.add_credential(&email, passkey.clone()).await.unwrap();
+
+        assert_eq!(
+            store.add_credential(&other, passkey).await,
+            Err(CredentialStoreError::CredentialAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registration_can_only_be_taken_once() {
+        let mut store = HashMapCredentialStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let (_, state) = WEBAUTHN
+            .start_passkey_registration(
+                user_unique_id(&UserId::default()),
+                email.as_ref(),
+                email.as_ref(),
+                None,
+            )
+            .unwrap();
+
+        store.add_registration(email.clone(), state).await.unwrap();
+
+        assert!(store.take_registration(&email).await.is_ok());
+        assert!(matches!(
+            store.take_registration(&email).await,
+            Err(CredentialStoreError::CeremonyNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_login_can_only_be_taken_once() {
+        let mut store = HashMapCredentialStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let passkey = register_passkey(&email);
+        let (_, state) = WEBAUTHN.start_passkey_authentication(&[passkey]).unwrap();
+        let ceremony_id = PasskeyCeremonyId::default();
+
+        store
+            .add_login(
+                ceremony_id.clone(),
+                PasskeyLogin {
+                    email: email.clone(),
+                    login_attempt_id: None,
+                    state,
+                },
+            )
+            .await
+            .unwrap();
+
+        let ceremony = store.take_login(&ceremony_id).await.unwrap();
+        assert_eq!(ceremony.state.email, email);
+        assert!(!ceremony.is_expired());
+        assert!(matches!(
+            store.take_login(&ceremony_id).await,
+            Err(CredentialStoreError::CeremonyNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let mut store = HashMapCredentialStore::new();
+        let (expired, live) = (
+            Email::parse("expired@example.com").unwrap(),
+            Email::parse("live@example.com").unwrap(),
+        );
+        let registration = |email: &Email| {
+            WEBAUTHN
+                .start_passkey_registration(
+                    user_unique_id(&UserId::default()),
+                    email.as_ref(),
+                    email.as_ref(),
+                    None,
+                )
+                .unwrap()
+                .1
+        };
+
+        let mut ceremony = PasskeyCeremony::new(registration(&expired));
+        ceremony.issued_at -= PASSKEY_CEREMONY_TTL_SECONDS as i64;
+        store.registrations.insert(expired.clone(), ceremony);
+        store
+            .add_registration(live.clone(), registration(&live))
+            .await
+            .unwrap();
+
+        assert_eq!(store.purge_expired().await, Ok(1));
+        assert_eq!(
+            store.take_registration(&expired).await.unwrap_err(),
+            CredentialStoreError::CeremonyNotFound
+        );
+        assert!(store.take_registration(&live).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut store = HashMapCredentialStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let new_email = Email::parse("new@example.com").unwrap();
+        let passkey = register_passkey(&email);
+        store.add_credential(&email, passkey.clone()).await.unwrap();
+
+        store.change_email(&email, &new_email).await.unwrap();
+
+        assert!(store.get_credentials(&email).await.unwrap().is_empty());
+        let credentials = store.get_credentials(&new_email).await.unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].cred_id(), passkey.cred_id());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::domain::data_stores::{
+    RefreshTokenEntry, RefreshTokenFamilyId, RefreshTokenStore, RefreshTokenStoreError,
+};
+use crate::domain::RefreshToken;
+use std::collections::HashMap;
+
+// Refresh tokens keyed by their hash
+#[derive(Default)]
+pub struct HashMapRefreshTokenStore {
+    tokens: HashMap<String, RefreshTokenEntry>,
+}
+
+impl HashMapRefreshTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashMapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.insert(token.hash(), entry);
+
+        Ok(())
+    }
+
+    async fn use_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+        let entry = self
+            .tokens
+            .get_mut(&token.hash())
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        let previous = entry.clone();
+        entry.used = true;
+
+        Ok(previous)
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.retain(|_, entry| &entry.family_id != family_id);
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, RefreshTokenStoreError> {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, entry| !entry.is_expired());
+
+        Ok((before - self.tokens.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    fn entry(family_id: &RefreshTokenFamilyId) -> RefreshTokenEntry {
+        let email = Email::parse("user@example.com").unwrap();
+        RefreshTokenEntry::new(email, family_id.clone())
+    }
+
+    #[tokio::test]
+    async fn test_use_token_marks_it_used() {
+        let mut store = HashMapRefreshTokenStore::new();
+        let family_id = RefreshTokenFamilyId::default();
+        let token = RefreshToken::generate();
+
+        store.add_token(&token, entry(&family_id)).await.unwrap();
+
+        assert!(!store.use_token(&token).await.unwrap().used);
+        assert!(store.use_token(&token).await.unwrap().used);
+        assert_eq!(
+            store.use_token(&RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashMapRefreshTokenStore::new();
+        let family_id = RefreshTokenFamilyId::default();
+        let (first, second, other) = (
+            RefreshToken::generate(),
+            RefreshToken::generate(),
+            RefreshToken::generate(),
+        );
+
+        store.add_token(&first, entry(&family_id)).await.unwrap();
+        store.add_token(&second, entry(&family_id)).await.unwrap();
+        store
+            .add_token(&other, entry(&RefreshTokenFamilyId::default()))
+            .await
+            .unwrap();
+
+        store.revoke_family(&family_id).await.unwrap();
+
+        assert_eq!(
+            store.use_token(&first).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.use_token(&second).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store.use_token(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let mut store = HashMapRefreshTokenStore::new();
+        let family_id = RefreshTokenFamilyId::default();
+        let (expired, live) = (RefreshToken::generate(), RefreshToken::generate());
+
+        let mut expired_entry = entry(&family_id);
+        expired_entry.expires_at = chrono::Utc::now().timestamp() - 1;
+        store.add_token(&expired, expired_entry).await.unwrap();
+        store.add_token(&live, entry(&family_id)).await.unwrap();
+
+        assert_eq!(store.purge_expired().await, Ok(1));
+        assert_eq!(
+            store.use_token(&expired).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store.use_token(&live).await.is_ok());
+    }
+}
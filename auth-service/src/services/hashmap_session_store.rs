@@ -0,0 +1,220 @@
+use crate::domain::data_stores::{Session, SessionId, SessionStore, SessionStoreError};
+use crate::domain::Email;
+use std::collections::HashMap;
+
+// Sessions keyed by their id
+#[derive(Default)]
+pub struct HashMapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+}
+
+impl HashMapSessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashMapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .filter(|session| !session.is_expired())
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        Ok(self
+            .sessions
+            .values()
+            .filter(|session| &session.email == email && !session.is_expired())
+            .cloned()
+            .collect())
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        timestamp: i64,
+    ) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id)
+            .filter(|session| !session.is_expired())
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = timestamp;
+
+        Ok(())
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        match self.sessions.get(id) {
+            Some(session) if &session.email == email => {
+                self.sessions.remove(id);
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| &session.email != email);
+
+        Ok(())
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), SessionStoreError> {
+        for session in self.sessions.values_mut() {
+            if &session.email == email {
+                session.email = new_email.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, SessionStoreError> {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, session| !session.is_expired());
+
+        Ok((before - self.sessions.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::constants::REFRESH_TOKEN_TTL_SECONDS;
+
+    fn session(email: &str) -> Session {
+        let email = Email::parse(email).unwrap();
+        Session::new(
+            SessionId::default(),
+            email,
+            Some("127.0.0.1".to_owned()),
+            Some("test".to_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_listed_per_user() {
+        let mut store = HashMapSessionStore::new();
+        let (first, second) = (session("user@example.com"), session("user@example.com"));
+        let other = session("other@example.com");
+
+        for session in [&first, &second, &other] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        let mut sessions = store.get_sessions(&first.email).await.unwrap();
+        sessions.sort_by(|a, b| a.id.as_ref().cmp(b.id.as_ref()));
+        let mut expected = vec![first.clone(), second];
+        expected.sort_by(|a, b| a.id.as_ref().cmp(b.id.as_ref()));
+        assert_eq!(sessions, expected);
+        assert_eq!(store.get_session(&other.id).await, Ok(other));
+    }
+
+    #[tokio::test]
+    async fn test_remove_session_only_for_its_user() {
+        let mut store = HashMapSessionStore::new();
+        let session = session("user@example.com");
+        let other = Email::parse("other@example.com").unwrap();
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(
+            store.remove_session(&other, &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(
+            store.remove_session(&session.email, &session.id).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashMapSessionStore::new();
+        let (first, second) = (session("user@example.com"), session("user@example.com"));
+        let other = session("other@example.com");
+        for session in [&first, &second, &other] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        store.remove_sessions(&first.email).await.unwrap();
+
+        assert_eq!(store.get_sessions(&first.email).await, Ok(vec![]));
+        assert!(store.get_session(&other.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_touch_keeps_session_alive() {
+        let mut store = HashMapSessionStore::new();
+        let mut session = session("user@example.com");
+        session.last_seen_at -= *REFRESH_TOKEN_TTL_SECONDS as i64;
+        store.add_session(session.clone()).await.unwrap();
+
+        // An expired session cannot be brought back
+        assert_eq!(
+            store.touch_session(&session.id, session.created_at).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+
+        session.last_seen_at += 1;
+        store.add_session(session.clone()).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        store.touch_session(&session.id, now).await.unwrap();
+        assert_eq!(
+            store.get_session(&session.id).await.unwrap().last_seen_at,
+            now
+        );
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let mut store = HashMapSessionStore::new();
+        let (mut expired, live) = (session("user@example.com"), session("user@example.com"));
+        expired.last_seen_at -= *REFRESH_TOKEN_TTL_SECONDS as i64;
+        store.add_session(expired).await.unwrap();
+        store.add_session(live.clone()).await.unwrap();
+
+        assert_eq!(store.purge_expired().await, Ok(1));
+        assert_eq!(store.get_sessions(&live.email).await, Ok(vec![live]));
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut store = HashMapSessionStore::new();
+        let (moving, other) = (session("user@example.com"), session("other@example.com"));
+        let new_email = Email::parse("new@example.com").unwrap();
+        for session in [&moving, &other] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        store.change_email(&moving.email, &new_email).await.unwrap();
+
+        assert_eq!(store.get_sessions(&moving.email).await, Ok(vec![]));
+        let moved = store.get_sessions(&new_email).await.unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].id, moving.id);
+        assert_eq!(store.get_session(&other.id).await, Ok(other));
+    }
+}
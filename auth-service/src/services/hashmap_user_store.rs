@@ -1,37 +1,63 @@
 use crate::domain::data_stores::{
     UserStore, UserStoreError,
-    UserStoreError::{IncorrectCredentials, UserAlreadyExists, UserNotFound},
+    UserStoreError::{IncorrectCredentials, UnexpectedError, UserAlreadyExists, UserNotFound},
 };
-use crate::domain::user::User;
-use crate::domain::{Email, Password};
-use std::collections::HashMap;
+use crate::domain::user::{NewUser, TwoFAMethod, User, UserId};
+use crate::domain::{Email, HashedPassword, Password, RecoveryCode, TotpSecret};
+use std::collections::{HashMap, HashSet};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct HashMapUserStore {
-    users: HashMap<Email, User>,
+    users: HashMap<UserId, User>,
+    // Which user each address belongs to
+    user_ids: HashMap<Email, UserId>,
+    // Hashes of each user's unused recovery codes
+    recovery_codes: HashMap<UserId, HashSet<String>>,
 }
 
 impl HashMapUserStore {
     pub fn new() -> Self {
         HashMapUserStore {
             users: HashMap::new(),
+            user_ids: HashMap::new(),
+            recovery_codes: HashMap::new(),
         }
     }
+
+    fn user_id(&self, email: &Email) -> Result<&UserId, UserStoreError> {
+        self.user_ids.get(email).ok_or(UserNotFound)
+    }
+
+    fn user_mut(&mut self, email: &Email) -> Result<&mut User, UserStoreError> {
+        let id = self.user_ids.get(email).ok_or(UserNotFound)?;
+        self.users.get_mut(id).ok_or(UserNotFound)
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashMapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+    async fn add_user(&mut self, user: NewUser) -> Result<(), UserStoreError> {
+        if self.user_ids.contains_key(&user.email) {
             return Err(UserAlreadyExists);
         }
-        self.users.insert(user.email.clone(), user);
+
+        let password = HashedPassword::parse_password(&user.password)
+            .await
+            .map_err(|_| UnexpectedError)?;
+        let user = User::new(user.email, password, user.requires_2fa);
+        self.user_ids.insert(user.email.clone(), user.id.clone());
+        self.users.insert(user.id.clone(), user);
 
         Ok(())
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        self.users.get(email).cloned().ok_or(UserNotFound)
+        let id = self.user_id(email)?;
+        self.get_user_by_id(id).await
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users.get(id).cloned().ok_or(UserNotFound)
     }
 
     async fn validate_user(
@@ -41,11 +67,150 @@ impl UserStore for HashMapUserStore {
     ) -> Result<User, UserStoreError> {
         let user = self.get_user(email).await?;
 
-        if &user.password == password {
-            Ok(user)
-        } else {
-            Err(IncorrectCredentials)
+        match user.password.verify(password).await {
+            Ok(true) => Ok(user),
+            Ok(false) => Err(IncorrectCredentials),
+            Err(_) => Err(UnexpectedError),
+        }
+    }
+
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.pending_totp_secret = Some(secret);
+
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        step: u64,
+    ) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.requires_2fa = true;
+        user.two_fa_method = TwoFAMethod::Totp;
+        user.totp_secret = Some(secret);
+        user.pending_totp_secret = None;
+        user.totp_last_used_step = Some(step);
+
+        Ok(())
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        let user = self.user_mut(email)?;
+        if user.totp_last_used_step.is_some_and(|last| last >= step) {
+            return Ok(false);
+        }
+        user.totp_last_used_step = Some(step);
+
+        Ok(true)
+    }
+
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), UserStoreError> {
+        let id = self.user_id(email)?.clone();
+        self.recovery_codes
+            .insert(id, codes.iter().map(RecoveryCode::hash).collect());
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<bool, UserStoreError> {
+        let id = self.user_id(email)?.clone();
+
+        Ok(self
+            .recovery_codes
+            .get_mut(&id)
+            .is_some_and(|hashes| hashes.remove(&code.hash())))
+    }
+
+    async fn set_tokens_valid_after(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.tokens_valid_after = Some(timestamp);
+
+        Ok(())
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.password = HashedPassword::parse_password(password)
+            .await
+            .map_err(|_| UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.email_verified = true;
+
+        Ok(())
+    }
+
+    async fn set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let user = self.user_mut(email)?;
+        user.verification_sent_at = Some(timestamp);
+
+        Ok(())
+    }
+
+    async fn try_set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+        cutoff: i64,
+    ) -> Result<bool, UserStoreError> {
+        let Ok(user) = self.user_mut(email) else {
+            return Ok(false);
+        };
+        let throttled = user.verification_sent_at.is_some_and(|sent_at| sent_at > cutoff);
+        if user.email_verified || throttled {
+            return Ok(false);
+        }
+        user.verification_sent_at = Some(timestamp);
+
+        Ok(true)
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        if self.user_ids.contains_key(new_email) {
+            return Err(UserAlreadyExists);
         }
+
+        // Everything else is kept by id, so only the address needs moving
+        let id = self.user_ids.remove(email).ok_or(UserNotFound)?;
+        self.users.get_mut(&id).ok_or(UserNotFound)?.email = new_email.clone();
+        self.user_ids.insert(new_email.clone(), id);
+
+        Ok(())
     }
 }
 
@@ -57,7 +222,7 @@ mod tests {
     async fn test_add_user() {
         let mut store = HashMapUserStore::new();
 
-        let user = User {
+        let user = NewUser {
             email: Email::parse("a@abc.com").unwrap(),
             password: Password::parse("password").unwrap(),
             requires_2fa: true,
@@ -70,12 +235,12 @@ mod tests {
     async fn test_add_user_already_exists() {
         let mut store = HashMapUserStore::new();
 
-        let user = User::new(
+        let user = NewUser::new(
             Email::parse("a@abc.com").unwrap(),
             Password::parse("password_a").unwrap(),
             true,
         );
-        let user_two = User::new(
+        let user_two = NewUser::new(
             Email::parse("a@abc.com").unwrap(),
             Password::parse("password").unwrap(),
             true,
@@ -93,15 +258,16 @@ mod tests {
         let password = Password::parse("password").unwrap();
 
         store
-            .add_user(User::new(email.clone(), password.clone(), true))
+            .add_user(NewUser::new(email.clone(), password.clone(), true))
             .await
             .expect("insert user failed");
 
         let user = store.get_user(&email).await.expect("Failed to find user");
 
         assert_eq!(user.email, email);
-        assert_eq!(user.password, password);
-        assert_eq!(user.requires_2fa, true);
+        assert_ne!(user.password.as_ref(), password.as_ref());
+        assert_eq!(user.password.verify(&password).await, Ok(true));
+        assert!(user.requires_2fa);
     }
 
     #[tokio::test]
@@ -113,6 +279,28 @@ mod tests {
         assert!(matches!(result, Err(UserNotFound)));
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let other = Email::parse("other@example.com").unwrap();
+
+        for email in [&email, &other] {
+            store
+                .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+                .await
+                .expect("Failed to insert user");
+        }
+        let user = store.get_user(&email).await.unwrap();
+        assert_ne!(user.id, store.get_user(&other).await.unwrap().id);
+
+        assert_eq!(store.get_user_by_id(&user.id).await, Ok(user));
+        assert_eq!(
+            store.get_user_by_id(&UserId::default()).await,
+            Err(UserNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_user_not_found() {
         let store = HashMapUserStore::new();
@@ -132,14 +320,14 @@ mod tests {
         let password = Password::parse("password").unwrap();
 
         store
-            .add_user(User::new(email.clone(), password, true))
+            .add_user(NewUser::new(email.clone(), password, true))
             .await
             .expect("Failed to insert user");
 
         let result = store
             .validate_user(&email, &Password::parse("password111").unwrap())
             .await;
-        assert!(matches!(result, Err(_)));
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -149,7 +337,7 @@ mod tests {
         let email = Email::parse("user@example.com").unwrap();
         let password = Password::parse("password").unwrap();
 
-        let user = User::new(email.clone(), password.clone(), true);
+        let user = NewUser::new(email.clone(), password.clone(), true);
 
         store
             .add_user(user.clone())
@@ -157,6 +345,194 @@ mod tests {
             .expect("Failed to insert user");
 
         let result = store.validate_user(&email, &password).await.unwrap();
-        assert_eq!(result, user);
+        assert_eq!(result.email, user.email);
+        assert_eq!(result.requires_2fa, user.requires_2fa);
+    }
+
+    #[tokio::test]
+    async fn test_enable_totp() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let secret = TotpSecret::generate();
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+            .await
+            .expect("Failed to insert user");
+        store
+            .set_pending_totp_secret(&email, secret.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user(&email).await.unwrap().pending_totp_secret,
+            Some(secret.clone())
+        );
+
+        store.enable_totp(&email, secret.clone(), 10).await.unwrap();
+
+        let user = store.get_user(&email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+        assert_eq!(user.totp_secret, Some(secret));
+        assert_eq!(user.pending_totp_secret, None);
+        assert_eq!(user.totp_last_used_step, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_use_totp_step_rejects_replay() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert_eq!(store.use_totp_step(&email, 1).await, Err(UserNotFound));
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), true))
+            .await
+            .expect("Failed to insert user");
+
+        assert_eq!(store.use_totp_step(&email, 10).await, Ok(true));
+        assert_eq!(store.use_totp_step(&email, 10).await, Ok(false));
+        assert_eq!(store.use_totp_step(&email, 9).await, Ok(false));
+        assert_eq!(store.use_totp_step(&email, 11).await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_codes_are_single_use_and_replaced() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), true))
+            .await
+            .expect("Failed to insert user");
+
+        let codes = RecoveryCode::generate_set();
+        store.set_recovery_codes(&email, &codes).await.unwrap();
+        let id = store.user_id(&email).unwrap();
+        assert!(!store.recovery_codes[id].contains(codes[0].as_ref()));
+
+        assert_eq!(store.use_recovery_code(&email, &codes[0]).await, Ok(true));
+        assert_eq!(store.use_recovery_code(&email, &codes[0]).await, Ok(false));
+
+        store
+            .set_recovery_codes(&email, &RecoveryCode::generate_set())
+            .await
+            .unwrap();
+        assert_eq!(store.use_recovery_code(&email, &codes[1]).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_set_tokens_valid_after() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert_eq!(
+            store.set_tokens_valid_after(&email, 100).await,
+            Err(UserNotFound)
+        );
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+            .await
+            .expect("Failed to insert user");
+        assert_eq!(store.get_user(&email).await.unwrap().tokens_valid_after, None);
+
+        store.set_tokens_valid_after(&email, 100).await.unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.tokens_valid_after, Some(100));
+        assert!(!user.accepts_token_issued_at(100));
+        assert!(user.accepts_token_issued_at(101));
+    }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let (old, new) = (Password::parse("password").unwrap(), Password::parse("password123").unwrap());
+
+        assert_eq!(store.update_password(&email, &new).await, Err(UserNotFound));
+
+        store
+            .add_user(NewUser::new(email.clone(), old.clone(), false))
+            .await
+            .expect("Failed to insert user");
+        store.update_password(&email, &new).await.unwrap();
+
+        assert_eq!(store.validate_user(&email, &old).await, Err(IncorrectCredentials));
+        assert!(store.validate_user(&email, &new).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_email_verification() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert_eq!(store.mark_email_verified(&email).await, Err(UserNotFound));
+        assert_eq!(store.set_verification_sent_at(&email, 100).await, Err(UserNotFound));
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+            .await
+            .expect("Failed to insert user");
+        let user = store.get_user(&email).await.unwrap();
+        assert!(!user.email_verified);
+        assert_eq!(user.verification_sent_at, None);
+
+        store.set_verification_sent_at(&email, 100).await.unwrap();
+        store.mark_email_verified(&email).await.unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert!(user.email_verified);
+        assert_eq!(user.verification_sent_at, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_try_set_verification_sent_at() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+
+        assert_eq!(store.try_set_verification_sent_at(&email, 100, 0).await, Ok(false));
+
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+            .await
+            .expect("Failed to insert user");
+        assert_eq!(store.try_set_verification_sent_at(&email, 100, 0).await, Ok(true));
+        // Sent after the cutoff
+        assert_eq!(store.try_set_verification_sent_at(&email, 150, 99).await, Ok(false));
+        assert_eq!(store.try_set_verification_sent_at(&email, 200, 100).await, Ok(true));
+        assert_eq!(store.get_user(&email).await.unwrap().verification_sent_at, Some(200));
+
+        store.mark_email_verified(&email).await.unwrap();
+        assert_eq!(store.try_set_verification_sent_at(&email, 300, 300).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut store = HashMapUserStore::new();
+        let email = Email::parse("user@example.com").unwrap();
+        let new_email = Email::parse("new@example.com").unwrap();
+        let taken = Email::parse("taken@example.com").unwrap();
+        let password = Password::parse("password").unwrap();
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(store.change_email(&email, &new_email).await, Err(UserNotFound));
+
+        for email in [&email, &taken] {
+            store
+                .add_user(NewUser::new(email.clone(), password.clone(), false))
+                .await
+                .expect("Failed to insert user");
+        }
+        store.set_recovery_codes(&email, &codes).await.unwrap();
+        let id = store.get_user(&email).await.unwrap().id;
+
+        assert_eq!(store.change_email(&email, &taken).await, Err(UserAlreadyExists));
+        store.change_email(&email, &new_email).await.unwrap();
+
+        assert_eq!(store.get_user(&email).await, Err(UserNotFound));
+        let user = store.validate_user(&new_email, &password).await.unwrap();
+        assert_eq!(user.email, new_email);
+        assert_eq!(user.id, id);
+        assert_eq!(store.use_recovery_code(&new_email, &codes[0]).await, Ok(true));
     }
 }
@@ -1,19 +1,36 @@
-use crate::domain::data_stores::{BannedTokenStore};
-use std::collections::HashSet;
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use chrono::Utc;
+use std::collections::HashMap;
 
+// Banned jtis mapped to the expiry of the token they belong to
 #[derive(Default)]
 pub struct HashSetBannedTokenStore {
-    pub store: HashSet<String>,
+    pub store: HashMap<String, usize>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashSetBannedTokenStore {
-    async fn add(&mut self, token: String) -> () {
-        self.store.insert(token);
+    async fn add(&mut self, jti: String, exp: usize) -> Result<bool, BannedTokenStoreError> {
+        if self.contains(&jti).await? {
+            return Ok(false);
+        }
+        if exp > Utc::now().timestamp() as usize {
+            self.store.insert(jti, exp);
+        }
+        Ok(true)
     }
 
-    async fn contains(&self, token: &str) -> bool {
-        self.store.contains(token)
+    async fn contains(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let now = Utc::now().timestamp() as usize;
+        Ok(self.store.get(jti).is_some_and(|exp| *exp > now))
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, BannedTokenStoreError> {
+        let now = Utc::now().timestamp() as usize;
+        let before = self.store.len();
+        self.store.retain(|_, exp| *exp > now);
+
+        Ok((before - self.store.len()) as u64)
     }
 }
 
@@ -24,6 +41,33 @@ mod tests {
     #[tokio::test]
     async fn test_add() {
         let mut store = HashSetBannedTokenStore::default();
-        store.add("some_token".to_string()).await
+        let exp = (Utc::now().timestamp() + 600) as usize;
+        assert_eq!(store.add("some_jti".to_string(), exp).await, Ok(true));
+        assert_eq!(store.contains("some_jti").await, Ok(true));
+        assert_eq!(store.contains("other_jti").await, Ok(false));
+
+        assert_eq!(store.add("some_jti".to_string(), exp).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let mut store = HashSetBannedTokenStore::default();
+        let now = Utc::now().timestamp();
+
+        store.store.insert("stale".to_owned(), (now - 1) as usize);
+        store
+            .add("expired".to_owned(), (now - 1) as usize)
+            .await
+            .unwrap();
+        store
+            .add("live".to_owned(), (now + 600) as usize)
+            .await
+            .unwrap();
+
+        assert_eq!(store.contains("expired").await, Ok(false));
+        assert_eq!(store.purge_expired().await, Ok(1));
+        assert!(!store.store.contains_key("stale"));
+        assert_eq!(store.contains("live").await, Ok(true));
+        assert_eq!(store.purge_expired().await, Ok(0));
     }
 }
@@ -0,0 +1,292 @@
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Email, EmailClient, Secret};
+
+// Sends emails through the REST API of a transactional email provider. The request body
+// is Postmark's, which providers with a Postmark-compatible API accept as well.
+pub struct HttpEmailClient {
+    http_client: reqwest::Client,
+    url: String,
+    sender: String,
+}
+
+// Where the email provider's API is and how to log in to it
+#[derive(Debug, Clone)]
+pub struct HttpEmailConfig {
+    // The endpoint emails are posted to, e.g. https://api.postmarkapp.com/email
+    pub url: String,
+    // The header the API token is sent in, e.g. X-Postmark-Server-Token
+    pub token_header: String,
+    pub token: Secret,
+    // The `From` of every email, either a bare address or `Name <address>`
+    pub sender: String,
+    // How long one request may take, connecting included
+    pub timeout: Duration,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    text_body: &'a str,
+}
+
+// The error bodies of the common providers: Postmark sends `Message`,
+// others `message` or a list of `errors`
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(alias = "Message")]
+    message: Option<String>,
+    #[serde(default)]
+    errors: Vec<ErrorResponse>,
+}
+
+impl ErrorResponse {
+    fn describe(self) -> Option<String> {
+        let messages: Vec<String> = self
+            .message
+            .into_iter()
+            .chain(self.errors.into_iter().filter_map(ErrorResponse::describe))
+            .collect();
+        (!messages.is_empty()).then(|| messages.join("; "))
+    }
+}
+
+impl HttpEmailClient {
+    pub fn new(config: HttpEmailConfig) -> Result<Self, String> {
+        let token_header = HeaderName::from_bytes(config.token_header.as_bytes())
+            .map_err(|err| format!("Invalid token header {}: {}", config.token_header, err))?;
+        let mut token = HeaderValue::from_str(config.token.as_ref())
+            .map_err(|err| format!("Invalid API token: {}", err))?;
+        token.set_sensitive(true);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(token_header, token);
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            http_client,
+            url: config.url,
+            sender: config.sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for HttpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        let request = SendEmailRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            subject,
+            text_body: content,
+        };
+
+        let response = self
+            .http_client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(describe_request_error)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .ok()
+            .and_then(ErrorResponse::describe)
+            .unwrap_or(body);
+        Err(format!("Email provider returned {}: {}", status, message))
+    }
+}
+
+fn describe_request_error(err: reqwest::Error) -> String {
+    if err.is_timeout() {
+        "Timed out sending email".to_owned()
+    } else {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(server: &MockServer) -> HttpEmailConfig {
+        HttpEmailConfig {
+            url: format!("{}/email", server.uri()),
+            token_header: "X-Postmark-Server-Token".to_owned(),
+            token: "token".to_owned().into(),
+            sender: "Auth <no-reply@example.com>".to_owned(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn recipient() -> Email {
+        Email::parse("user@example.com").unwrap()
+    }
+
+    async fn send_with_response(response: ResponseTemplate) -> Result<(), String> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+
+        let client = HttpEmailClient::new(config(&server)).unwrap();
+        client.send_email(&recipient(), "Subject", "Content").await
+    }
+
+    #[tokio::test]
+    async fn test_sends_email() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/email"))
+            .and(header("X-Postmark-Server-Token", "token"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(json!({
+                "From": "Auth <no-reply@example.com>",
+                "To": "user@example.com",
+                "Subject": "Your 2FA code",
+                "TextBody": "Your code is 123456",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ErrorCode": 0})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = HttpEmailClient::new(config(&server)).unwrap();
+        client
+            .send_email(&recipient(), "Your 2FA code", "Your code is 123456")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sends_token_in_configured_header() {
+        let server = MockServer::start().await;
+        Mock::given(header("Authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = HttpEmailClient::new(HttpEmailConfig {
+            token_header: "Authorization".to_owned(),
+            token: "Bearer token".to_owned().into(),
+            ..config(&server)
+        })
+        .unwrap();
+        client
+            .send_email(&recipient(), "Subject", "Content")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reports_error_message() {
+        let response = ResponseTemplate::new(422).set_body_json(json!({
+            "ErrorCode": 300,
+            "Message": "Invalid 'To' address",
+        }));
+
+        assert_eq!(
+            send_with_response(response).await,
+            Err(
+                "Email provider returned 422 Unprocessable Entity: Invalid 'To' address".to_owned()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_error_list() {
+        let response = ResponseTemplate::new(400).set_body_json(json!({
+            "errors": [
+                {"message": "The from address is not verified", "field": "from"},
+                {"message": "Subject is required", "field": "subject"},
+            ],
+        }));
+
+        assert_eq!(
+            send_with_response(response).await,
+            Err("Email provider returned 400 Bad Request: \
+                 The from address is not verified; Subject is required"
+                .to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_unstructured_error() {
+        let response = ResponseTemplate::new(503).set_body_string("Service Unavailable");
+
+        assert_eq!(
+            send_with_response(response).await,
+            Err("Email provider returned 503 Service Unavailable: Service Unavailable".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_times_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = HttpEmailClient::new(HttpEmailConfig {
+            timeout: Duration::from_millis(200),
+            ..config(&server)
+        })
+        .unwrap();
+        let result = client.send_email(&recipient(), "Subject", "Content").await;
+        assert_eq!(result, Err("Timed out sending email".to_owned()));
+    }
+
+    #[test]
+    fn test_debug_hides_token() {
+        let config = HttpEmailConfig {
+            url: "http://localhost/email".to_owned(),
+            token_header: "X-Postmark-Server-Token".to_owned(),
+            token: "secret-token".to_owned().into(),
+            sender: "no-reply@example.com".to_owned(),
+            timeout: Duration::from_secs(5),
+        };
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("X-Postmark-Server-Token"));
+        assert!(!debug.contains("secret-token"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_token_header() {
+        let result = HttpEmailClient::new(HttpEmailConfig {
+            url: "http://localhost/email".to_owned(),
+            token_header: "Not a header".to_owned(),
+            token: "token".to_owned().into(),
+            sender: "no-reply@example.com".to_owned(),
+            timeout: Duration::from_secs(5),
+        });
+        assert!(result.is_err());
+    }
+}
@@ -1,4 +1,15 @@
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
 pub mod hashmap_2fa_token_store;
-pub mod mock_email_client;
\ No newline at end of file
+pub mod hashmap_credential_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
+pub mod http_email_client;
+pub mod mock_email_client;
+pub mod postgres_user_store;
+pub mod redis_banned_token_store;
+pub mod redis_refresh_token_store;
+pub mod redis_session_store;
+pub mod redis_two_fa_code_store;
+pub mod smtp_email_client;
+pub mod sqlite_store;
\ No newline at end of file
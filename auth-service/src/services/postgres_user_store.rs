@@ -0,0 +1,329 @@
+use crate::domain::data_stores::{
+    UserStore, UserStoreError,
+    UserStoreError::{IncorrectCredentials, UnexpectedError, UserAlreadyExists, UserNotFound},
+};
+use crate::domain::user::{NewUser, TwoFAMethod, User, UserId};
+use crate::domain::{Email, HashedPassword, Password, RecoveryCode, TotpSecret};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+pub struct PostgresUserStore {
+    pool: PgPool,
+}
+
+impl PostgresUserStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for PostgresUserStore {
+    async fn add_user(&mut self, user: NewUser) -> Result<(), UserStoreError> {
+        let password = HashedPassword::parse_password(&user.password)
+            .await
+            .map_err(|_| UnexpectedError)?;
+
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, requires_2fa, email_verified) VALUES ($1::uuid, $2, $3, $4, FALSE)",
+        )
+        .bind(UserId::default().as_ref())
+        .bind(user.email.as_ref())
+        .bind(password.as_ref())
+        .bind(user.requires_2fa)
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        Ok(())
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let row = sqlx::query(
+            "SELECT id::text AS id, email, password_hash, requires_2fa, two_fa_method, totp_secret, pending_totp_secret, totp_last_used_step, tokens_valid_after, email_verified, verification_sent_at FROM users WHERE email = $1",
+        )
+        .bind(email.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        user_from_row(&row)
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let row = sqlx::query(
+            "SELECT id::text AS id, email, password_hash, requires_2fa, two_fa_method, totp_secret, pending_totp_secret, totp_last_used_step, tokens_valid_after, email_verified, verification_sent_at FROM users WHERE id = $1::uuid",
+        )
+        .bind(id.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        user_from_row(&row)
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError> {
+        let user = self.get_user(email).await?;
+
+        match user.password.verify(password).await {
+            Ok(true) => Ok(user),
+            Ok(false) => Err(IncorrectCredentials),
+            Err(_) => Err(UnexpectedError),
+        }
+    }
+
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET pending_totp_secret = $1 WHERE email = $2")
+            .bind(secret.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        step: u64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET requires_2fa = TRUE, two_fa_method = $1, totp_secret = $2, pending_totp_secret = NULL, totp_last_used_step = $3 WHERE email = $4",
+        )
+        .bind(TwoFAMethod::Totp.as_ref())
+        .bind(secret.as_ref())
+        .bind(step as i64)
+        .bind(email.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        // A single conditional update, so two requests racing with the same code cannot both win
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_used_step = $1 WHERE email = $2 AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)",
+        )
+        .bind(step as i64)
+        .bind(email.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            // Either the step was already used or there is no such user
+            self.get_user(email).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), UserStoreError> {
+        // The old set must not survive a partially written new one
+        let mut tx = self.pool.begin().await.map_err(map_sqlx_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&mut *tx)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        for code in codes {
+            sqlx::query("INSERT INTO recovery_codes (email, code_hash) VALUES ($1, $2)")
+                .bind(email.as_ref())
+                .bind(code.hash())
+                .execute(&mut *tx)
+                .await
+                .map_err(map_sqlx_error)?;
+        }
+
+        tx.commit().await.map_err(map_sqlx_error)
+    }
+
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query("DELETE FROM recovery_codes WHERE email = $1 AND code_hash = $2")
+            .bind(email.as_ref())
+            .bind(code.hash())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            self.get_user(email).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    async fn set_tokens_valid_after(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET tokens_valid_after = $1 WHERE email = $2")
+            .bind(timestamp)
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let password = HashedPassword::parse_password(password)
+            .await
+            .map_err(|_| UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET password_hash = $1 WHERE email = $2")
+            .bind(password.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email_verified = TRUE WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET verification_sent_at = $1 WHERE email = $2")
+            .bind(timestamp)
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+
+    // One conditional update, so that concurrent requests, on any replica, cannot both pass
+    async fn try_set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+        cutoff: i64,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET verification_sent_at = $1 WHERE email = $2 AND NOT email_verified AND (verification_sent_at IS NULL OR verification_sent_at <= $3)",
+        )
+        .bind(timestamp)
+        .bind(email.as_ref())
+        .bind(cutoff)
+        .execute(&self.pool)
+        .await
+        .map_err(map_sqlx_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Recovery codes follow by ON UPDATE CASCADE; a taken address violates the primary key
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email = $1 WHERE email = $2")
+            .bind(new_email.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserNotFound);
+        }
+        Ok(())
+    }
+}
+
+fn user_from_row(row: &PgRow) -> Result<User, UserStoreError> {
+    let id = UserId::parse(row.get("id")).map_err(|_| UnexpectedError)?;
+    let email = Email::parse(row.get("email")).map_err(|_| UnexpectedError)?;
+    let password = HashedPassword::parse(row.get("password_hash")).map_err(|_| UnexpectedError)?;
+    let parse_secret = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(TotpSecret::parse)
+            .transpose()
+            .map_err(|_| UnexpectedError)
+    };
+
+    Ok(User {
+        id,
+        two_fa_method: TwoFAMethod::parse(row.get("two_fa_method")).map_err(|_| UnexpectedError)?,
+        totp_secret: parse_secret("totp_secret")?,
+        pending_totp_secret: parse_secret("pending_totp_secret")?,
+        totp_last_used_step: row
+            .get::<Option<i64>, _>("totp_last_used_step")
+            .map(|step| step as u64),
+        tokens_valid_after: row.get("tokens_valid_after"),
+        email_verified: row.get("email_verified"),
+        verification_sent_at: row.get("verification_sent_at"),
+        ..User::new(email, password, row.get("requires_2fa"))
+    })
+}
+
+// Translate database failures into the store's error type
+fn map_sqlx_error(err: sqlx::Error) -> UserStoreError {
+    match err {
+        sqlx::Error::RowNotFound => UserNotFound,
+        sqlx::Error::Database(err) if err.is_unique_violation() => UserAlreadyExists,
+        // Rows that hang off a user, such as recovery codes, need the user to exist
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => UserNotFound,
+        _ => UnexpectedError,
+    }
+}
@@ -0,0 +1,112 @@
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+#[derive(Clone)]
+pub struct RedisBannedTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisBannedTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for RedisBannedTokenStore {
+    async fn add(&mut self, jti: String, exp: usize) -> Result<bool, BannedTokenStoreError> {
+        // Keep the key exactly as long as the token itself would be accepted
+        let ttl = exp as i64 - Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(true);
+        }
+
+        // SET NX only sets a key that does not exist yet, and replies nil otherwise
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(ttl as u64));
+        let set: Option<String> = self
+            .conn
+            .set_options(get_key(&jti), true, options)
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(set.is_some())
+    }
+
+    async fn contains(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        // ConnectionManager is a cheap handle onto a shared multiplexed connection
+        let mut conn = self.conn.clone();
+
+        conn.exists(get_key(jti))
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)
+    }
+
+    // Redis drops each key itself once its TTL runs out
+    async fn purge_expired(&mut self) -> Result<u64, BannedTokenStoreError> {
+        Ok(0)
+    }
+}
+
+// We are using a key prefix to prevent collisions and organize data!
+const BANNED_TOKEN_KEY_PREFIX: &str = "banned_jti:";
+
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_redis_connection;
+    use crate::utils::constants::REDIS_HOST_NAME;
+    use uuid::Uuid;
+
+    async fn store() -> RedisBannedTokenStore {
+        let conn = get_redis_connection(&REDIS_HOST_NAME)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisBannedTokenStore::new(conn)
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_add_and_contains() {
+        let mut store = store().await;
+        let jti = Uuid::new_v4().to_string();
+        let exp = (Utc::now().timestamp() + 600) as usize;
+
+        assert_eq!(store.contains(&jti).await, Ok(false));
+        assert_eq!(store.add(jti.clone(), exp).await, Ok(true));
+        assert_eq!(store.contains(&jti).await, Ok(true));
+
+        assert_eq!(store.add(jti.clone(), exp).await, Ok(false));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_key_expires_with_token() {
+        let mut store = store().await;
+        let jti = Uuid::new_v4().to_string();
+        let exp = (Utc::now().timestamp() + 600) as usize;
+
+        store.add(jti.clone(), exp).await.unwrap();
+
+        let ttl: i64 = store.conn.ttl(get_key(&jti)).await.unwrap();
+        assert!(ttl > 590 && ttl <= 600);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_expired_token_is_not_stored() {
+        let mut store = store().await;
+        let jti = Uuid::new_v4().to_string();
+        let exp = (Utc::now().timestamp() - 1) as usize;
+
+        assert_eq!(store.add(jti.clone(), exp).await, Ok(true));
+        assert_eq!(store.contains(&jti).await, Ok(false));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::domain::data_stores::{
+    RefreshTokenEntry, RefreshTokenFamilyId, RefreshTokenStore, RefreshTokenStoreError,
+};
+use crate::domain::{Email, RefreshToken};
+use crate::utils::constants::REFRESH_TOKEN_TTL_SECONDS;
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+pub struct RedisRefreshTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+// Tokens are not indexed by family. Revoking a family instead leaves a marker
+// that outlives every token the family could still hold.
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let ttl = entry.expires_at - Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let value = serde_json::to_string(&StoredEntry {
+            email: entry.email.as_ref().to_owned(),
+            family_id: entry.family_id.as_ref().to_owned(),
+            issued_at_ms: entry.issued_at,
+            expires_at: entry.expires_at,
+        })
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let hash = token.hash();
+        // A token counts as used once its use counter has gone past one
+        let uses = if entry.used { 1 } else { 0 };
+
+        redis::pipe()
+            .atomic()
+            .set_ex(get_key(&hash), value, ttl as u64)
+            .ignore()
+            .set_ex(get_uses_key(&hash), uses, ttl as u64)
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+
+    async fn use_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+        let hash = token.hash();
+
+        let value: Option<String> = self
+            .conn
+            .get(get_key(&hash))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let value = value.ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        let stored: LoadedEntry =
+            serde_json::from_str(&value).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let family_id = RefreshTokenFamilyId::parse(stored.family_id)
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let revoked: bool = self
+            .conn
+            .exists(get_revoked_family_key(&family_id))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        if revoked {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        // INCR is atomic, so of two concurrent uses only one sees the counter go to 1
+        let uses: u64 = self
+            .conn
+            .incr(get_uses_key(&hash), 1)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(RefreshTokenEntry {
+            email: Email::parse(&stored.email)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            family_id,
+            issued_at: stored.issued_at_ms.unwrap_or(stored.issued_at * 1000),
+            expires_at: stored.expires_at,
+            used: uses > 1,
+        })
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // No token of the family can be valid for longer than a full TTL from now
+        self.conn
+            .set_ex::<_, _, ()>(
+                get_revoked_family_key(family_id),
+                true,
+                *REFRESH_TOKEN_TTL_SECONDS,
+            )
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+
+    // Redis drops tokens and family markers itself once their TTL runs out
+    async fn purge_expired(&mut self) -> Result<u64, RefreshTokenStoreError> {
+        Ok(0)
+    }
+}
+
+#[derive(Serialize)]
+struct StoredEntry {
+    email: String,
+    family_id: String,
+    issued_at_ms: i64,
+    expires_at: i64,
+}
+
+// A `StoredEntry` as read back, which may have been stored by an older version
+#[derive(Deserialize)]
+struct LoadedEntry {
+    email: String,
+    family_id: String,
+    // Seconds since the epoch, in entries stored before `issued_at_ms` was. Missing from
+    // entries stored before either, which then count as issued at the epoch.
+    #[serde(default)]
+    issued_at: i64,
+    #[serde(default)]
+    issued_at_ms: Option<i64>,
+    expires_at: i64,
+}
+
+const REFRESH_TOKEN_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_USES_PREFIX: &str = "refresh_token_uses:";
+const REVOKED_FAMILY_PREFIX: &str = "refresh_family_revoked:";
+
+fn get_key(hash: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_PREFIX, hash)
+}
+
+fn get_uses_key(hash: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_USES_PREFIX, hash)
+}
+
+fn get_revoked_family_key(family_id: &RefreshTokenFamilyId) -> String {
+    format!("{}{}", REVOKED_FAMILY_PREFIX, family_id.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_redis_connection;
+    use crate::utils::constants::REDIS_HOST_NAME;
+
+    async fn store() -> RedisRefreshTokenStore {
+        let conn = get_redis_connection(&REDIS_HOST_NAME)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisRefreshTokenStore::new(conn)
+    }
+
+    fn entry(family_id: &RefreshTokenFamilyId) -> RefreshTokenEntry {
+        let email = Email::parse("user@example.com").unwrap();
+        RefreshTokenEntry::new(email, family_id.clone())
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_use_token_detects_replay() {
+        let mut store = store().await;
+        let family_id = RefreshTokenFamilyId::default();
+        let token = RefreshToken::generate();
+
+        store.add_token(&token, entry(&family_id)).await.unwrap();
+
+        let first = store.use_token(&token).await.unwrap();
+        assert!(!first.used);
+        assert_eq!(first.family_id, family_id);
+        assert!(store.use_token(&token).await.unwrap().used);
+        assert_eq!(
+            store.use_token(&RefreshToken::generate()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_keys_expire_with_token() {
+        let mut store = store().await;
+        let token = RefreshToken::generate();
+        let mut entry = entry(&RefreshTokenFamilyId::default());
+        entry.expires_at = Utc::now().timestamp() + 600;
+
+        store.add_token(&token, entry).await.unwrap();
+
+        let ttl: i64 = store.conn.ttl(get_key(&token.hash())).await.unwrap();
+        assert!(ttl > 590 && ttl <= 600);
+        let ttl: i64 = store.conn.ttl(get_uses_key(&token.hash())).await.unwrap();
+        assert!(ttl > 590 && ttl <= 600);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_revoke_family() {
+        let mut store = store().await;
+        let family_id = RefreshTokenFamilyId::default();
+        let (token, other) = (RefreshToken::generate(), RefreshToken::generate());
+
+        store.add_token(&token, entry(&family_id)).await.unwrap();
+        store
+            .add_token(&other, entry(&RefreshTokenFamilyId::default()))
+            .await
+            .unwrap();
+
+        store.revoke_family(&family_id).await.unwrap();
+
+        assert_eq!(
+            store.use_token(&token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store.use_token(&other).await.is_ok());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::domain::data_stores::{Session, SessionId, SessionStore, SessionStoreError};
+use crate::domain::Email;
+use crate::utils::constants::REFRESH_TOKEN_TTL_SECONDS;
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    // Writes the session with a TTL matching its expiry, and indexes it under its user
+    async fn store(&mut self, session: &Session) -> Result<(), SessionStoreError> {
+        let ttl = session.expires_at() - Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let value = serde_json::to_string(&StoredSession::from(session))
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        redis::pipe()
+            .atomic()
+            .set_ex(get_key(&session.id), value, ttl as u64)
+            .ignore()
+            .sadd(get_user_key(&session.email), session.id.as_ref())
+            .ignore()
+            // The index outlives every session it lists
+            .expire(
+                get_user_key(&session.email),
+                *REFRESH_TOKEN_TTL_SECONDS as i64,
+            )
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+}
+
+// Each session is a key of its own that Redis expires. A set per user lists
+// their session ids; ids whose session has expired are dropped when listed.
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.store(&session).await
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(get_key(id))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(SessionStoreError::SessionNotFound)?;
+        StoredSession::parse(&value, id.clone())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.clone();
+        let ids: Vec<String> = conn
+            .smembers(get_user_key(email))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| format!("{}{}", SESSION_PREFIX, id))
+            .collect();
+        let values: Vec<Option<String>> = conn
+            .mget(keys)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = vec![];
+        let mut expired = vec![];
+        for (id, value) in ids.into_iter().zip(values) {
+            let Some(value) = value else {
+                expired.push(id);
+                continue;
+            };
+            let id = SessionId::parse(id).map_err(|_| SessionStoreError::UnexpectedError)?;
+            sessions.push(StoredSession::parse(&value, id)?);
+        }
+
+        if !expired.is_empty() {
+            conn.srem::<_, _, ()>(get_user_key(email), expired)
+                .await
+                .map_err(|_| SessionStoreError::UnexpectedError)?;
+        }
+
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        timestamp: i64,
+    ) -> Result<(), SessionStoreError> {
+        let mut session = self.get_session(id).await?;
+        session.last_seen_at = timestamp;
+
+        self.store(&session).await
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        let session = self.get_session(id).await?;
+        if &session.email != email {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        redis::pipe()
+            .atomic()
+            .del(get_key(id))
+            .ignore()
+            .srem(get_user_key(email), id.as_ref())
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        let ids: Vec<String> = self
+            .conn
+            .smembers(get_user_key(email))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut keys: Vec<String> = ids
+            .iter()
+            .map(|id| format!("{}{}", SESSION_PREFIX, id))
+            .collect();
+        keys.push(get_user_key(email));
+
+        self.conn
+            .del::<_, ()>(keys)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+
+    // Each session is rewritten, which also lists it under the new address
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), SessionStoreError> {
+        for mut session in self.get_sessions(email).await? {
+            session.email = new_email.clone();
+            self.store(&session).await?;
+        }
+
+        self.conn
+            .del::<_, ()>(get_user_key(email))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+
+    // Redis drops sessions itself once their TTL runs out
+    async fn purge_expired(&mut self) -> Result<u64, SessionStoreError> {
+        Ok(0)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    email: String,
+    created_at: i64,
+    last_seen_at: i64,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl From<&Session> for StoredSession {
+    fn from(session: &Session) -> Self {
+        Self {
+            email: session.email.as_ref().to_owned(),
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            ip: session.ip.clone(),
+            user_agent: session.user_agent.clone(),
+        }
+    }
+}
+
+impl StoredSession {
+    // The id is not stored in the value, as it is already part of the key
+    fn parse(value: &str, id: SessionId) -> Result<Session, SessionStoreError> {
+        let stored: StoredSession =
+            serde_json::from_str(value).map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(Session {
+            id,
+            email: Email::parse(&stored.email).map_err(|_| SessionStoreError::UnexpectedError)?,
+            created_at: stored.created_at,
+            last_seen_at: stored.last_seen_at,
+            ip: stored.ip,
+            user_agent: stored.user_agent,
+        })
+    }
+}
+
+const SESSION_PREFIX: &str = "session:";
+const USER_SESSIONS_PREFIX: &str = "user_sessions:";
+
+fn get_key(id: &SessionId) -> String {
+    format!("{}{}", SESSION_PREFIX, id.as_ref())
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_PREFIX, email.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_redis_connection;
+    use crate::utils::constants::REDIS_HOST_NAME;
+    use uuid::Uuid;
+
+    async fn store() -> RedisSessionStore {
+        let conn = get_redis_connection(&REDIS_HOST_NAME)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisSessionStore::new(conn)
+    }
+
+    fn session(email: &Email) -> Session {
+        Session::new(
+            SessionId::default(),
+            email.clone(),
+            Some("127.0.0.1".to_owned()),
+            Some("test".to_owned()),
+        )
+    }
+
+    // Every test gets users of its own, as they share the Redis server
+    fn email() -> Email {
+        Email::parse(&format!("{}@example.com", Uuid::new_v4())).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_sessions_are_listed_and_removed() {
+        let mut store = store().await;
+        let (email, other) = (email(), email());
+        let (first, second) = (session(&email), session(&email));
+        store.add_session(first.clone()).await.unwrap();
+        store.add_session(second.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&first.id).await, Ok(first.clone()));
+        assert_eq!(store.get_sessions(&email).await.unwrap().len(), 2);
+
+        assert_eq!(
+            store.remove_session(&other, &first.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        store.remove_session(&email, &first.id).await.unwrap();
+        assert_eq!(store.get_sessions(&email).await, Ok(vec![second]));
+
+        store.remove_sessions(&email).await.unwrap();
+        assert_eq!(store.get_sessions(&email).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_touch_extends_ttl() {
+        let mut store = store().await;
+        let mut session = session(&email());
+        session.last_seen_at -= 600;
+        store.add_session(session.clone()).await.unwrap();
+
+        let ttl: i64 = store.conn.ttl(get_key(&session.id)).await.unwrap();
+        assert!(ttl <= *REFRESH_TOKEN_TTL_SECONDS as i64 - 600);
+
+        let now = Utc::now().timestamp();
+        store.touch_session(&session.id, now).await.unwrap();
+        let ttl: i64 = store.conn.ttl(get_key(&session.id)).await.unwrap();
+        assert!(ttl > *REFRESH_TOKEN_TTL_SECONDS as i64 - 10);
+        assert_eq!(
+            store.get_session(&session.id).await.unwrap().last_seen_at,
+            now
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_change_email() {
+        let mut store = store().await;
+        let (email, new_email) = (email(), email());
+        let session = session(&email);
+        store.add_session(session.clone()).await.unwrap();
+
+        store.change_email(&email, &new_email).await.unwrap();
+
+        assert_eq!(store.get_sessions(&email).await, Ok(vec![]));
+        let moved = store.get_sessions(&new_email).await.unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].id, session.id);
+        assert_eq!(
+            store.get_session(&session.id).await.unwrap().email,
+            new_email
+        );
+    }
+}
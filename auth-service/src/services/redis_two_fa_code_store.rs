@@ -0,0 +1,345 @@
+use crate::domain::data_stores::{
+    LoginAttemptId, TwoFACode, TwoFACodeEntry, TwoFACodeStore, TwoFACodeStoreError,
+};
+use crate::domain::Email;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    // Counts a failed attempt at the pending code, unless there is none, in which case it
+    // returns -1. The attempts key is given the code key's TTL, so it never outlives the code.
+    static ref RECORD_FAILED_ATTEMPT: Script = Script::new(
+        r"
+        local ttl = redis.call('PTTL', KEYS[1])
+        if ttl < 0 then
+            return -1
+        end
+        local attempts = redis.call('INCR', KEYS[2])
+        redis.call('PEXPIRE', KEYS[2], ttl)
+        return attempts
+        "
+    );
+
+    // Moves the code and its attempts from the first two keys to the last two. Either may
+    // have expired, in which case there is nothing of it to move. RENAME keeps the TTL, so
+    // the code expires when it would have.
+    static ref CHANGE_EMAIL: Script = Script::new(
+        r"
+        for i = 1, 2 do
+            if redis.call('EXISTS', KEYS[i]) == 1 then
+                redis.call('RENAME', KEYS[i], KEYS[i + 2])
+            end
+        end
+        return 0
+        "
+    );
+}
+
+#[derive(Clone)]
+pub struct RedisTwoFACodeStore {
+    conn: ConnectionManager,
+}
+
+impl RedisTwoFACodeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    // Both keys expire shortly after the code does, so that a code entered just too late
+    // is reported as expired rather than missing
+    async fn store_entry(
+        &mut self,
+        email: &Email,
+        entry: &TwoFACodeEntry,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let ttl = entry.expires_at() + EXPIRED_CODE_GRACE_SECONDS - Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        let value = serde_json::to_string(&StoredEntry {
+            login_attempt_id: entry.login_attempt_id.as_ref().to_owned(),
+            code: entry.code.as_ref().to_owned(),
+            issued_at: entry.issued_at,
+            ttl_seconds: entry.ttl_seconds,
+        })
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        redis::pipe()
+            .atomic()
+            .set_ex(get_key(email), value, ttl as u64)
+            .ignore()
+            .set_ex(get_attempts_key(email), entry.failed_attempts, ttl as u64)
+            .ignore()
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for RedisTwoFACodeStore {
+    async fn add_code(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let entry = TwoFACodeEntry::new(login_attempt_id, code);
+        self.store_entry(&email, &entry).await
+    }
+
+    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.conn
+            .del::<_, ()>(&[get_key(email), get_attempts_key(email)])
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn get_code(&self, email: &Email) -> Result<TwoFACodeEntry, TwoFACodeStoreError> {
+        let mut conn = self.conn.clone();
+
+        let value: Option<String> = conn
+            .get(get_key(email))
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let value = value.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let failed_attempts: Option<u32> = conn
+            .get(get_attempts_key(email))
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let stored: StoredEntry =
+            serde_json::from_str(&value).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(TwoFACodeEntry {
+            login_attempt_id: LoginAttemptId::parse(stored.login_attempt_id)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            code: TwoFACode::parse(stored.code)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            issued_at: stored.issued_at,
+            ttl_seconds: stored.ttl_seconds,
+            failed_attempts: failed_attempts.unwrap_or(0),
+        })
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        // One script, so the code cannot expire between the check and the count
+        let attempts: i64 = RECORD_FAILED_ATTEMPT
+            .key(get_key(email))
+            .key(get_attempts_key(email))
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        u32::try_from(attempts).map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // One script, so the code cannot expire between the check and the rename
+        CHANGE_EMAIL
+            .key(get_key(email))
+            .key(get_attempts_key(email))
+            .key(get_key(new_email))
+            .key(get_attempts_key(new_email))
+            .invoke_async::<()>(&mut self.conn)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    // Redis drops both keys itself once their TTL runs out
+    async fn purge_expired(&mut self) -> Result<u64, TwoFACodeStoreError> {
+        Ok(0)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    login_attempt_id: String,
+    code: String,
+    issued_at: i64,
+    ttl_seconds: u64,
+}
+
+const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+// How long an expired code is kept around to be reported as such
+const EXPIRED_CODE_GRACE_SECONDS: i64 = 60;
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+}
+
+fn get_attempts_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_PREFIX, email.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_redis_connection;
+    use crate::utils::constants::{REDIS_HOST_NAME, TWO_FA_CODE_TTL_SECONDS};
+    use uuid::Uuid;
+
+    async fn store() -> RedisTwoFACodeStore {
+        let conn = get_redis_connection(&REDIS_HOST_NAME)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisTwoFACodeStore::new(conn)
+    }
+
+    fn random_email() -> Email {
+        Email::parse(&format!("{}@example.com", Uuid::new_v4())).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_add_and_get_code() {
+        let mut store = store().await;
+        let email = random_email();
+        let login_attempt = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        store
+            .add_code(email.clone(), login_attempt.clone(), code.clone())
+            .await
+            .unwrap();
+
+        let entry = store.get_code(&email).await.unwrap();
+        assert_eq!(entry.login_attempt_id, login_attempt);
+        assert_eq!(entry.code, code);
+        assert_eq!(entry.failed_attempts, 0);
+        assert!(!entry.is_expired());
+
+        let ttl: i64 = store.conn.ttl(get_key(&email)).await.unwrap();
+        assert!(ttl > *TWO_FA_CODE_TTL_SECONDS as i64);
+        assert!(ttl <= *TWO_FA_CODE_TTL_SECONDS as i64 + EXPIRED_CODE_GRACE_SECONDS);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_expired_code_is_kept() {
+        let mut store = store().await;
+        let email = random_email();
+        let mut entry = TwoFACodeEntry::new(LoginAttemptId::default(), TwoFACode::default());
+        entry.issued_at -= entry.ttl_seconds as i64;
+
+        store.store_entry(&email, &entry).await.unwrap();
+
+        let stored = store.get_code(&email).await.unwrap();
+        assert!(stored.is_expired());
+        assert_eq!(stored, entry);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_remove_code() {
+        let mut store = store().await;
+        let email = random_email();
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        assert!(store.remove_code(&email).await.is_ok());
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_get_code_missing() {
+        let store = store().await;
+
+        assert_eq!(
+            store.get_code(&random_email()).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_record_failed_attempt() {
+        let mut store = store().await;
+        let email = random_email();
+
+        assert_eq!(
+            store.record_failed_attempt(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+        assert_eq!(store.get_code(&email).await.unwrap().failed_attempts, 2);
+
+        // The attempts go when the code does
+        let code_ttl: i64 = store.conn.pttl(get_key(&email)).await.unwrap();
+        let attempts_ttl: i64 = store.conn.pttl(get_attempts_key(&email)).await.unwrap();
+        assert!(attempts_ttl > 0 && attempts_ttl <= code_ttl);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_record_failed_attempt_without_code_leaves_nothing_behind() {
+        let mut store = store().await;
+        let email = random_email();
+
+        assert_eq!(
+            store.record_failed_attempt(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        let exists: bool = store.conn.exists(get_attempts_key(&email)).await.unwrap();
+        assert!(!exists);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_change_email() {
+        let mut store = store().await;
+        let (email, new_email) = (random_email(), random_email());
+
+        // Nothing pending, nothing to move
+        assert_eq!(store.change_email(&email, &new_email).await, Ok(()));
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+        store.record_failed_attempt(&email).await.unwrap();
+        let entry = store.get_code(&email).await.unwrap();
+
+        store.change_email(&email, &new_email).await.unwrap();
+
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(store.get_code(&new_email).await, Ok(entry));
+        let ttl: i64 = store.conn.ttl(get_key(&new_email)).await.unwrap();
+        assert!(ttl > *TWO_FA_CODE_TTL_SECONDS as i64);
+
+        // A code gone by the time of the move is no error
+        store.conn.del::<_, ()>(get_key(&new_email)).await.unwrap();
+        assert_eq!(
+            store.change_email(&new_email, &random_email()).await,
+            Ok(())
+        );
+    }
+}
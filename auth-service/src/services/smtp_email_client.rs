@@ -0,0 +1,378 @@
+use std::time::Duration;
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::domain::{Email, EmailClient, Secret};
+
+// Sends emails through an SMTP server, such as the relay of an email provider.
+// Connections are pooled, so sending a burst of 2FA codes does not reconnect for each.
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+    timeout: Duration,
+}
+
+// Where the SMTP server is and how to talk to it
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    // Left unset, the usual port for `tls`
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    // Username and password, for servers that want a login
+    pub credentials: Option<(String, Secret)>,
+    // The `From` of every email, either a bare address or `Name <address>`
+    pub sender: String,
+    // How long sending one email may take, connecting included
+    pub timeout: Duration,
+    // How many connections are kept open for reuse
+    pub pool_size: u32,
+}
+
+// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    // Connect in plain text, then upgrade with STARTTLS; the server must offer it
+    StartTls,
+    // TLS from the start, also known as SMTPS
+    Tls,
+    // No TLS at all, which is only fit for a local mail sink
+    None,
+}
+
+impl SmtpTls {
+    pub fn parse(tls: &str) -> Result<Self, String> {
+        match tls {
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            "none" => Ok(SmtpTls::None),
+            other => Err(format!("Unknown SMTP TLS mode: {}", other)),
+        }
+    }
+
+    fn default_port(self) -> u16 {
+        match self {
+            SmtpTls::StartTls => 587,
+            SmtpTls::Tls => 465,
+            SmtpTls::None => 25,
+        }
+    }
+}
+
+impl SmtpEmailClient {
+    // Nothing is connected to until the first email is sent
+    pub fn new(config: SmtpConfig) -> Result<Self, String> {
+        let sender = config
+            .sender
+            .parse::<Mailbox>()
+            .map_err(|err| format!("Invalid sender {}: {}", config.sender, err))?;
+
+        let tls = match config.tls {
+            SmtpTls::StartTls => Tls::Required(tls_parameters(&config.host)?),
+            SmtpTls::Tls => Tls::Wrapper(tls_parameters(&config.host)?),
+            SmtpTls::None => Tls::None,
+        };
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            .port(config.port.unwrap_or(config.tls.default_port()))
+            .tls(tls)
+            .timeout(Some(config.timeout))
+            .pool_config(PoolConfig::new().max_size(config.pool_size));
+        if let Some((username, password)) = config.credentials {
+            builder = builder.credentials(Credentials::new(username, password.as_ref().to_owned()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            sender,
+            timeout: config.timeout,
+        })
+    }
+}
+
+fn tls_parameters(host: &str) -> Result<TlsParameters, String> {
+    TlsParameters::new(host.to_owned()).map_err(|err| err.to_string())
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        let recipient = recipient
+            .as_ref()
+            .parse::<Mailbox>()
+            .map_err(|err| format!("Invalid recipient {}: {}", recipient, err))?;
+
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(content.to_owned())
+            .map_err(|err| err.to_string())?;
+
+        // The transport only bounds the time taken to connect, not a server that stops answering
+        match tokio::time::timeout(self.timeout, self.transport.send(message)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(_) => Err("Timed out sending email".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    // An email as the fake server took it in
+    #[derive(Debug, Clone, Default)]
+    struct ReceivedEmail {
+        // The decoded `AUTH PLAIN` response, if the client logged in
+        auth: Option<String>,
+        recipients: Vec<String>,
+        data: String,
+    }
+
+    // Just enough of an SMTP server to take mail from the client, without TLS
+    #[derive(Clone, Default)]
+    struct FakeSmtpServer {
+        received: Arc<Mutex<Vec<ReceivedEmail>>>,
+        connections: Arc<AtomicUsize>,
+        // Refuse every recipient, as for an unknown mailbox
+        reject_recipients: bool,
+        // Accept connections but never greet, as a hung server would
+        silent: bool,
+    }
+
+    impl FakeSmtpServer {
+        // Start listening, returning the port
+        async fn start(&self) -> u16 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            let server = self.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    server.connections.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(server.clone().serve(stream));
+                }
+            });
+
+            port
+        }
+
+        async fn serve(self, stream: TcpStream) -> std::io::Result<()> {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            if self.silent {
+                // Until the client gives up
+                while lines.next_line().await?.is_some() {}
+                return Ok(());
+            }
+
+            writer.write_all(b"220 fake ESMTP\r\n").await?;
+            let mut email = ReceivedEmail::default();
+            while let Some(line) = lines.next_line().await? {
+                let mut words = line.split(' ');
+                let reply: &[u8] = match words.next().unwrap_or("").to_uppercase().as_str() {
+                    "EHLO" => b"250-fake\r\n250 AUTH PLAIN LOGIN\r\n",
+                    "AUTH" => {
+                        let response = words.nth(1).and_then(|word| STANDARD.decode(word).ok());
+                        email.auth = response.map(|bytes| String::from_utf8_lossy(&bytes).into());
+                        b"235 Authenticated\r\n"
+                    }
+                    "RCPT" if self.reject_recipients => b"550 No such mailbox\r\n",
+                    "RCPT" => {
+                        email.recipients.push(line.clone());
+                        b"250 OK\r\n"
+                    }
+                    "DATA" => {
+                        writer.write_all(b"354 Go ahead\r\n").await?;
+                        while let Some(line) = lines.next_line().await? {
+                            if line == "." {
+                                break;
+                            }
+                            email.data.push_str(&line);
+                            email.data.push('\n');
+                        }
+                        self.received
+                            .lock()
+                            .unwrap()
+                            .push(std::mem::take(&mut email));
+                        b"250 Queued\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 Bye\r\n").await?;
+                        return Ok(());
+                    }
+                    // MAIL, RSET, NOOP
+                    _ => b"250 OK\r\n",
+                };
+                writer.write_all(reply).await?;
+            }
+
+            Ok(())
+        }
+
+        fn received(&self) -> Vec<ReceivedEmail> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_owned(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            credentials: None,
+            sender: "Auth <no-reply@example.com>".to_owned(),
+            timeout: Duration::from_secs(5),
+            pool_size: 2,
+        }
+    }
+
+    fn recipient() -> Email {
+        Email::parse("user@example.com").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sends_email() {
+        let server = FakeSmtpServer::default();
+        let client = SmtpEmailClient::new(config(server.start().await)).unwrap();
+
+        client
+            .send_email(&recipient(), "Your 2FA code", "Your code is 123456")
+            .await
+            .unwrap();
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].auth, None);
+        assert_eq!(received[0].recipients, ["RCPT TO:<user@example.com>"]);
+        for line in [
+            "From: Auth <no-reply@example.com>",
+            "To: user@example.com",
+            "Subject: Your 2FA code",
+            "Content-Type: text/plain; charset=utf-8",
+            "Your code is 123456",
+        ] {
+            assert!(received[0].data.contains(line), "{} missing", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logs_in() {
+        let server = FakeSmtpServer::default();
+        let client = SmtpEmailClient::new(SmtpConfig {
+            credentials: Some(("user".to_owned(), "secret".to_owned().into())),
+            ..config(server.start().await)
+        })
+        .unwrap();
+
+        client
+            .send_email(&recipient(), "Subject", "Content")
+            .await
+            .unwrap();
+
+        assert_eq!(server.received()[0].auth.as_deref(), Some("\0user\0secret"));
+    }
+
+    #[tokio::test]
+    async fn test_reuses_connection() {
+        let server = FakeSmtpServer::default();
+        let client = SmtpEmailClient::new(config(server.start().await)).unwrap();
+
+        for _ in 0..3 {
+            client
+                .send_email(&recipient(), "Subject", "Content")
+                .await
+                .unwrap();
+            // The connection goes back to the pool in the background
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        assert_eq!(server.received().len(), 3);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reports_rejected_recipient() {
+        let server = FakeSmtpServer {
+            reject_recipients: true,
+            ..FakeSmtpServer::default()
+        };
+        let client = SmtpEmailClient::new(config(server.start().await)).unwrap();
+
+        let err = client
+            .send_email(&recipient(), "Subject", "Content")
+            .await
+            .unwrap_err();
+        assert!(err.contains("No such mailbox"), "{}", err);
+        assert!(server.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_times_out() {
+        let server = FakeSmtpServer {
+            silent: true,
+            ..FakeSmtpServer::default()
+        };
+        let client = SmtpEmailClient::new(SmtpConfig {
+            timeout: Duration::from_millis(200),
+            ..config(server.start().await)
+        })
+        .unwrap();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.send_email(&recipient(), "Subject", "Content"),
+        )
+        .await
+        .expect("The client did not time out");
+        assert_eq!(result, Err("Timed out sending email".to_owned()));
+    }
+
+    #[test]
+    fn test_rejects_invalid_sender() {
+        let result = SmtpEmailClient::new(SmtpConfig {
+            sender: "not an address".to_owned(),
+            ..config(25)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_debug_hides_password() {
+        let config = SmtpConfig {
+            credentials: Some(("user".to_owned(), "hunter2".to_owned().into())),
+            ..config(25)
+        };
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_parse_tls() {
+        assert_eq!(SmtpTls::parse("starttls"), Ok(SmtpTls::StartTls));
+        assert_eq!(SmtpTls::parse("tls"), Ok(SmtpTls::Tls));
+        assert_eq!(SmtpTls::parse("none"), Ok(SmtpTls::None));
+        assert!(SmtpTls::parse("ssl").is_err());
+    }
+}
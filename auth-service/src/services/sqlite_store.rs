@@ -0,0 +1,1289 @@
+use crate::domain::data_stores::{
+    BannedTokenStore, BannedTokenStoreError, LoginAttemptId, RefreshTokenEntry,
+    RefreshTokenFamilyId, RefreshTokenStore, RefreshTokenStoreError, Session, SessionId,
+    SessionStore, SessionStoreError, TwoFACode, TwoFACodeEntry, TwoFACodeStore,
+    TwoFACodeStoreError, UserStore, UserStoreError,
+};
+use crate::domain::user::{NewUser, TwoFAMethod, User, UserId};
+use crate::domain::{Email, HashedPassword, Password, RecoveryCode, RefreshToken, TotpSecret};
+use chrono::Utc;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow};
+use sqlx::{Row, SqlitePool};
+use std::path::Path;
+
+// A single SQLite database file backing users, banned tokens, 2FA codes, refresh tokens and sessions,
+// for deployments that cannot run Postgres and Redis.
+// The store is a cheap handle onto a shared pool, so one clone can be handed to each AppState slot.
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    // Open (or create) the database file and bring its schema up to date
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?;
+
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteStore {
+    async fn add_user(&mut self, user: NewUser) -> Result<(), UserStoreError> {
+        let password = HashedPassword::parse_password(&user.password)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, requires_2fa, email_verified) VALUES (?, ?, ?, ?, FALSE)",
+        )
+        .bind(UserId::default().as_ref())
+        .bind(user.email.as_ref())
+        .bind(password.as_ref())
+        .bind(user.requires_2fa)
+        .execute(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        Ok(())
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let row = sqlx::query(
+            "SELECT id, email, password_hash, requires_2fa, two_fa_method, totp_secret, pending_totp_secret, totp_last_used_step, tokens_valid_after, email_verified, verification_sent_at FROM users WHERE email = ?",
+        )
+        .bind(email.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        user_from_row(&row)
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let row = sqlx::query(
+            "SELECT id, email, password_hash, requires_2fa, two_fa_method, totp_secret, pending_totp_secret, totp_last_used_step, tokens_valid_after, email_verified, verification_sent_at FROM users WHERE id = ?",
+        )
+        .bind(id.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        user_from_row(&row)
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError> {
+        let user = self.get_user(email).await?;
+
+        match user.password.verify(password).await {
+            Ok(true) => Ok(user),
+            Ok(false) => Err(UserStoreError::IncorrectCredentials),
+            Err(_) => Err(UserStoreError::UnexpectedError),
+        }
+    }
+
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET pending_totp_secret = ? WHERE email = ?")
+            .bind(secret.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        step: u64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET requires_2fa = TRUE, two_fa_method = ?, totp_secret = ?, pending_totp_secret = NULL, totp_last_used_step = ? WHERE email = ?",
+        )
+        .bind(TwoFAMethod::Totp.as_ref())
+        .bind(secret.as_ref())
+        .bind(step as i64)
+        .bind(email.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        // A single conditional update, so two requests racing with the same code cannot both win
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_used_step = ? WHERE email = ? AND (totp_last_used_step IS NULL OR totp_last_used_step < ?)",
+        )
+        .bind(step as i64)
+        .bind(email.as_ref())
+        .bind(step as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            // Either the step was already used or there is no such user
+            self.get_user(email).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), UserStoreError> {
+        // The old set must not survive a partially written new one
+        let mut tx = self.pool.begin().await.map_err(map_user_error)?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&mut *tx)
+            .await
+            .map_err(map_user_error)?;
+
+        for code in codes {
+            sqlx::query("INSERT INTO recovery_codes (email, code_hash) VALUES (?, ?)")
+                .bind(email.as_ref())
+                .bind(code.hash())
+                .execute(&mut *tx)
+                .await
+                .map_err(map_user_error)?;
+        }
+
+        tx.commit().await.map_err(map_user_error)
+    }
+
+    async fn use_recovery_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query("DELETE FROM recovery_codes WHERE email = ? AND code_hash = ?")
+            .bind(email.as_ref())
+            .bind(code.hash())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            self.get_user(email).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    async fn set_tokens_valid_after(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET tokens_valid_after = ? WHERE email = ?")
+            .bind(timestamp)
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let password = HashedPassword::parse_password(password)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET password_hash = ? WHERE email = ?")
+            .bind(password.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email_verified = TRUE WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET verification_sent_at = ? WHERE email = ?")
+            .bind(timestamp)
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    // One conditional update, so that concurrent requests cannot both pass
+    async fn try_set_verification_sent_at(
+        &mut self,
+        email: &Email,
+        timestamp: i64,
+        cutoff: i64,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET verification_sent_at = ? WHERE email = ? AND NOT email_verified AND (verification_sent_at IS NULL OR verification_sent_at <= ?)",
+        )
+        .bind(timestamp)
+        .bind(email.as_ref())
+        .bind(cutoff)
+        .execute(&self.pool)
+        .await
+        .map_err(map_user_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Recovery codes follow by ON UPDATE CASCADE; a taken address violates the primary key
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email = ? WHERE email = ?")
+            .bind(new_email.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(map_user_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for SqliteStore {
+    async fn add(&mut self, jti: String, exp: usize) -> Result<bool, BannedTokenStoreError> {
+        // An expired entry left for the sweeper no longer counts, and is replaced
+        let result = sqlx::query(
+            "INSERT INTO banned_tokens (jti, expires_at) VALUES (?, ?) ON CONFLICT (jti) DO UPDATE SET expires_at = excluded.expires_at WHERE banned_tokens.expires_at <= ?",
+        )
+        .bind(jti)
+        .bind(exp as i64)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn contains(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let row = sqlx::query("SELECT 1 FROM banned_tokens WHERE jti = ? AND expires_at > ?")
+            .bind(jti)
+            .bind(Utc::now().timestamp())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(row.is_some())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, BannedTokenStoreError> {
+        sqlx::query("DELETE FROM banned_tokens WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for SqliteStore {
+    async fn add_code(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let entry = TwoFACodeEntry::new(login_attempt_id, code);
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO two_fa_codes (email, login_attempt_id, code, issued_at, expires_at, failed_attempts) VALUES (?, ?, ?, ?, ?, 0)",
+        )
+        .bind(email.as_ref())
+        .bind(entry.login_attempt_id.as_ref())
+        .bind(entry.code.as_ref())
+        .bind(entry.issued_at)
+        .bind(entry.expires_at())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query("DELETE FROM two_fa_codes WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_code(&self, email: &Email) -> Result<TwoFACodeEntry, TwoFACodeStoreError> {
+        let row = sqlx::query(
+            "SELECT login_attempt_id, code, issued_at, expires_at, failed_attempts FROM two_fa_codes WHERE email = ?",
+        )
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let issued_at: i64 = row.get("issued_at");
+        let expires_at: i64 = row.get("expires_at");
+
+        Ok(TwoFACodeEntry {
+            login_attempt_id: LoginAttemptId::parse(row.get("login_attempt_id"))
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            code: TwoFACode::parse(row.get("code"))
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            issued_at,
+            ttl_seconds: (expires_at - issued_at).max(0) as u64,
+            failed_attempts: row.get("failed_attempts"),
+        })
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        sqlx::query_scalar(
+            "UPDATE two_fa_codes SET failed_attempts = failed_attempts + 1 WHERE email = ? RETURNING failed_attempts",
+        )
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query("UPDATE OR REPLACE two_fa_codes SET email = ? WHERE email = ?")
+            .bind(new_email.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, TwoFACodeStoreError> {
+        sqlx::query("DELETE FROM two_fa_codes WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for SqliteStore {
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, email, family_id, issued_at, expires_at, used) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(token.hash())
+        .bind(entry.email.as_ref())
+        .bind(entry.family_id.as_ref())
+        .bind(entry.issued_at)
+        .bind(entry.expires_at)
+        .bind(entry.used)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn use_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+        // Only one of two concurrent requests with the same token gets to flip `used`
+        let rotated = sqlx::query(
+            "UPDATE refresh_tokens SET used = TRUE WHERE token_hash = ? AND used = FALSE RETURNING email, family_id, issued_at, expires_at",
+        )
+        .bind(token.hash())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        if let Some(row) = rotated {
+            return refresh_token_from_row(&row, false);
+        }
+
+        let row = sqlx::query(
+            "SELECT email, family_id, issued_at, expires_at FROM refresh_tokens WHERE token_hash = ?",
+        )
+        .bind(token.hash())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        refresh_token_from_row(&row, true)
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE family_id = ?")
+            .bind(family_id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, RefreshTokenStoreError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for SqliteStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO sessions (id, email, created_at, last_seen_at, expires_at, ip, user_agent) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(session.id.as_ref())
+        .bind(session.email.as_ref())
+        .bind(session.created_at)
+        .bind(session.last_seen_at)
+        .bind(session.expires_at())
+        .bind(&session.ip)
+        .bind(&session.user_agent)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let row = sqlx::query(
+            "SELECT id, email, created_at, last_seen_at, ip, user_agent FROM sessions WHERE id = ? AND expires_at > ?",
+        )
+        .bind(id.as_ref())
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?
+        .ok_or(SessionStoreError::SessionNotFound)?;
+
+        session_from_row(&row)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        sqlx::query(
+            "SELECT id, email, created_at, last_seen_at, ip, user_agent FROM sessions WHERE email = ? AND expires_at > ?",
+        )
+        .bind(email.as_ref())
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?
+        .iter()
+        .map(session_from_row)
+        .collect()
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: &SessionId,
+        timestamp: i64,
+    ) -> Result<(), SessionStoreError> {
+        let mut session = self.get_session(id).await?;
+        session.last_seen_at = timestamp;
+
+        let result = sqlx::query(
+            "UPDATE sessions SET last_seen_at = ?, expires_at = ? WHERE id = ? AND expires_at > ?",
+        )
+        .bind(session.last_seen_at)
+        .bind(session.expires_at())
+        .bind(id.as_ref())
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query("DELETE FROM sessions WHERE id = ? AND email = ?")
+            .bind(id.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        Ok(())
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query("DELETE FROM sessions WHERE email = ?")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), SessionStoreError> {
+        sqlx::query("UPDATE sessions SET email = ? WHERE email = ?")
+            .bind(new_email.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&mut self) -> Result<u64, SessionStoreError> {
+        sqlx::query("DELETE FROM sessions WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|_| SessionStoreError::UnexpectedError)
+    }
+}
+
+fn session_from_row(row: &SqliteRow) -> Result<Session, SessionStoreError> {
+    Ok(Session {
+        id: SessionId::parse(row.get("id")).map_err(|_| SessionStoreError::UnexpectedError)?,
+        email: Email::parse(row.get("email")).map_err(|_| SessionStoreError::UnexpectedError)?,
+        created_at: row.get("created_at"),
+        last_seen_at: row.get("last_seen_at"),
+        ip: row.get("ip"),
+        user_agent: row.get("user_agent"),
+    })
+}
+
+fn refresh_token_from_row(
+    row: &SqliteRow,
+    used: bool,
+) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+    Ok(RefreshTokenEntry {
+        email: Email::parse(row.get("email"))
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+        family_id: RefreshTokenFamilyId::parse(row.get("family_id"))
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+        issued_at: row.get("issued_at"),
+        expires_at: row.get("expires_at"),
+        used,
+    })
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, UserStoreError> {
+    let id = UserId::parse(row.get("id")).map_err(|_| UserStoreError::UnexpectedError)?;
+    let email = Email::parse(row.get("email")).map_err(|_| UserStoreError::UnexpectedError)?;
+    let password = HashedPassword::parse(row.get("password_hash"))
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+    let parse_secret = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(TotpSecret::parse)
+            .transpose()
+            .map_err(|_| UserStoreError::UnexpectedError)
+    };
+
+    Ok(User {
+        id,
+        two_fa_method: TwoFAMethod::parse(row.get("two_fa_method"))
+            .map_err(|_| UserStoreError::UnexpectedError)?,
+        totp_secret: parse_secret("totp_secret")?,
+        pending_totp_secret: parse_secret("pending_totp_secret")?,
+        totp_last_used_step: row
+            .get::<Option<i64>, _>("totp_last_used_step")
+            .map(|step| step as u64),
+        tokens_valid_after: row.get("tokens_valid_after"),
+        email_verified: row.get("email_verified"),
+        verification_sent_at: row.get("verification_sent_at"),
+        ..User::new(email, password, row.get("requires_2fa"))
+    })
+}
+
+fn map_user_error(err: sqlx::Error) -> UserStoreError {
+    match err {
+        sqlx::Error::RowNotFound => UserStoreError::UserNotFound,
+        sqlx::Error::Database(err) if err.is_unique_violation() => {
+            UserStoreError::UserAlreadyExists
+        }
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            UserStoreError::UserNotFound
+        }
+        _ => UserStoreError::UnexpectedError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::constants::REFRESH_TOKEN_TTL_SECONDS;
+    use tempfile::TempDir;
+
+    async fn store(dir: &TempDir) -> SqliteStore {
+        SqliteStore::open(dir.path().join("auth.db"))
+            .await
+            .expect("Failed to open SQLite store")
+    }
+
+    #[tokio::test]
+    async fn test_uses_wal_journal_mode() {
+        let dir = TempDir::new().unwrap();
+        let store = store(&dir).await;
+
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn test_user_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+        let password = Password::parse("password").unwrap();
+
+        let mut store = store(&dir).await;
+        store
+            .add_user(NewUser::new(email.clone(), password.clone(), true))
+            .await
+            .unwrap();
+        store.pool.close().await;
+
+        let store = self::store(&dir).await;
+        let user = store.validate_user(&email, &password).await.unwrap();
+        assert_eq!(user.email, email);
+        assert!(user.requires_2fa);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+        let new_email = Email::parse("new@example.com").unwrap();
+
+        let mut store = store(&dir).await;
+        store
+            .add_user(NewUser::new(email.clone(), Password::parse("password").unwrap(), false))
+            .await
+            .unwrap();
+        let id = store.get_user(&email).await.unwrap().id;
+        store.pool.close().await;
+
+        // The id stays the same across restarts and email changes
+        let mut store = self::store(&dir).await;
+        UserStore::change_email(&mut store, &email, &new_email)
+            .await
+            .unwrap();
+        let user = store.get_user_by_id(&id).await.unwrap();
+        assert_eq!(user.email, new_email);
+        assert_eq!(
+            store.get_user_by_id(&UserId::default()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_updated_password_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+        let (old, new) = (
+            Password::parse("password").unwrap(),
+            Password::parse("password123").unwrap(),
+        );
+
+        let mut store = store(&dir).await;
+        assert_eq!(
+            store.update_password(&email, &new).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        store
+            .add_user(NewUser::new(email.clone(), old.clone(), false))
+            .await
+            .unwrap();
+        store.update_password(&email, &new).await.unwrap();
+        store.pool.close().await;
+
+        let store = self::store(&dir).await;
+        assert_eq!(
+            store.validate_user(&email, &old).await,
+            Err(UserStoreError::IncorrectCredentials)
+        );
+        assert!(store.validate_user(&email, &new).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_try_set_verification_sent_at() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+        let mut store = store(&dir).await;
+
+        assert_eq!(
+            store.try_set_verification_sent_at(&email, 100, 0).await,
+            Ok(false)
+        );
+
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.try_set_verification_sent_at(&email, 100, 0).await,
+            Ok(true)
+        );
+        // Sent after the cutoff
+        assert_eq!(
+            store.try_set_verification_sent_at(&email, 150, 99).await,
+            Ok(false)
+        );
+        assert_eq!(
+            store.try_set_verification_sent_at(&email, 200, 100).await,
+            Ok(true)
+        );
+        assert_eq!(
+            store.get_user(&email).await.unwrap().verification_sent_at,
+            Some(200)
+        );
+
+        store.mark_email_verified(&email).await.unwrap();
+        assert_eq!(
+            store.try_set_verification_sent_at(&email, 300, 300).await,
+            Ok(false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_email_verification_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+
+        let mut store = store(&dir).await;
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert!(!user.email_verified);
+        assert_eq!(user.verification_sent_at, None);
+
+        store.set_verification_sent_at(&email, 100).await.unwrap();
+        store.mark_email_verified(&email).await.unwrap();
+        store.pool.close().await;
+
+        let store = self::store(&dir).await;
+        let user = store.get_user(&email).await.unwrap();
+        assert!(user.email_verified);
+        assert_eq!(user.verification_sent_at, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_add_user_already_exists() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let user = NewUser::new(
+            Email::parse("user@example.com").unwrap(),
+            Password::parse("password").unwrap(),
+            false,
+        );
+
+        assert_eq!(store.add_user(user.clone()).await, Ok(()));
+        assert_eq!(
+            store.add_user(user).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_incorrect_password() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .validate_user(&email, &Password::parse("password111").unwrap())
+                .await,
+            Err(UserStoreError::IncorrectCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_banned_token_expires() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let now = Utc::now().timestamp();
+
+        store.add("live".to_owned(), (now + 600) as usize).await.unwrap();
+        store.add("expired".to_owned(), (now - 1) as usize).await.unwrap();
+
+        assert_eq!(store.contains("live").await, Ok(true));
+        assert_eq!(store.contains("expired").await, Ok(false));
+        assert_eq!(store.contains("unknown").await, Ok(false));
+
+        assert_eq!(BannedTokenStore::purge_expired(&mut store).await, Ok(1));
+        assert_eq!(BannedTokenStore::purge_expired(&mut store).await, Ok(0));
+        assert_eq!(store.contains("live").await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_banned_token_is_only_added_once() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let now = Utc::now().timestamp();
+        let exp = (now + 600) as usize;
+
+        assert_eq!(store.add("jti".to_owned(), exp).await, Ok(true));
+        assert_eq!(store.add("jti".to_owned(), exp).await, Ok(false));
+
+        // Unless its ban has run out, which the sweeper may not have noticed yet
+        store
+            .add("stale".to_owned(), (now - 1) as usize)
+            .await
+            .unwrap();
+        assert_eq!(store.add("stale".to_owned(), exp).await, Ok(true));
+        assert_eq!(store.contains("stale").await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_two_fa_codes() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let login_attempt = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store
+            .add_code(email.clone(), login_attempt.clone(), code.clone())
+            .await
+            .unwrap();
+        let entry = store.get_code(&email).await.unwrap();
+        assert_eq!(entry.login_attempt_id, login_attempt);
+        assert_eq!(entry.code, code);
+        assert!(!entry.is_expired());
+
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+        assert_eq!(store.get_code(&email).await.unwrap().failed_attempts, 2);
+
+        store.remove_code(&email).await.unwrap();
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(
+            store.record_failed_attempt(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tokens() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let family_id = RefreshTokenFamilyId::default();
+        let (first, second) = (RefreshToken::generate(), RefreshToken::generate());
+        let entry = RefreshTokenEntry::new(email.clone(), family_id.clone());
+
+        store.add_token(&first, entry.clone()).await.unwrap();
+        store.add_token(&second, entry.clone()).await.unwrap();
+
+        // The first use rotates the token, the second is a replay
+        assert_eq!(store.use_token(&first).await, Ok(entry.clone()));
+        let replayed = store.use_token(&first).await.unwrap();
+        assert!(replayed.used);
+        assert_eq!(replayed.family_id, family_id);
+
+        store.revoke_family(&family_id).await.unwrap();
+        assert_eq!(
+            store.use_token(&second).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_codes_and_refresh_tokens() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let (expired, live) = (RefreshToken::generate(), RefreshToken::generate());
+        let entry = RefreshTokenEntry::new(email.clone(), RefreshTokenFamilyId::default());
+
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+        sqlx::query("UPDATE two_fa_codes SET expires_at = 0")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        store
+            .add_token(
+                &expired,
+                RefreshTokenEntry {
+                    expires_at: Utc::now().timestamp() - 1,
+                    ..entry.clone()
+                },
+            )
+            .await
+            .unwrap();
+        store.add_token(&live, entry).await.unwrap();
+
+        assert_eq!(TwoFACodeStore::purge_expired(&mut store).await, Ok(1));
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(RefreshTokenStore::purge_expired(&mut store).await, Ok(1));
+        assert_eq!(
+            store.use_token(&expired).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store.use_token(&live).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_totp_enrollment() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let secret = TotpSecret::generate();
+
+        assert_eq!(
+            store.set_pending_totp_secret(&email, secret.clone()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+        store
+            .set_pending_totp_secret(&email, secret.clone())
+            .await
+            .unwrap();
+        store.enable_totp(&email, secret.clone(), 10).await.unwrap();
+
+        let user = store.get_user(&email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+        assert_eq!(user.totp_secret, Some(secret));
+        assert_eq!(user.pending_totp_secret, None);
+
+        assert_eq!(store.use_totp_step(&email, 10).await, Ok(false));
+        assert_eq!(store.use_totp_step(&email, 11).await, Ok(true));
+        assert_eq!(store.use_totp_step(&email, 11).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_codes() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(
+            store.set_recovery_codes(&email, &codes).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                true,
+            ))
+            .await
+            .unwrap();
+        store.set_recovery_codes(&email, &codes).await.unwrap();
+
+        let stored: Vec<String> =
+            sqlx::query_scalar("SELECT code_hash FROM recovery_codes WHERE email = ?")
+                .bind(email.as_ref())
+                .fetch_all(&store.pool)
+                .await
+                .unwrap();
+        assert_eq!(stored.len(), codes.len());
+        assert!(!stored.contains(&codes[0].as_ref().to_owned()));
+
+        assert_eq!(store.use_recovery_code(&email, &codes[0]).await, Ok(true));
+        assert_eq!(store.use_recovery_code(&email, &codes[0]).await, Ok(false));
+
+        store
+            .set_recovery_codes(&email, &RecoveryCode::generate_set())
+            .await
+            .unwrap();
+        assert_eq!(store.use_recovery_code(&email, &codes[1]).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_valid_after_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let email = Email::parse("user@example.com").unwrap();
+
+        let mut store = store(&dir).await;
+        assert_eq!(
+            store.set_tokens_valid_after(&email, 100).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user(&email).await.unwrap().tokens_valid_after,
+            None
+        );
+        store.set_tokens_valid_after(&email, 100).await.unwrap();
+        store.pool.close().await;
+
+        let store = self::store(&dir).await;
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.tokens_valid_after, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let session = |email: &Email| {
+            Session::new(
+                SessionId::default(),
+                email.clone(),
+                Some("127.0.0.1".to_owned()),
+                None,
+            )
+        };
+        let first = session(&email);
+        let other = session(&Email::parse("other@example.com").unwrap());
+        let mut expired = session(&email);
+        expired.last_seen_at -= *REFRESH_TOKEN_TTL_SECONDS as i64;
+        for session in [&first, &other, &expired] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        assert_eq!(store.get_session(&first.id).await, Ok(first.clone()));
+        assert_eq!(store.get_sessions(&email).await, Ok(vec![first.clone()]));
+        assert_eq!(
+            store.touch_session(&expired.id, first.created_at).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(SessionStore::purge_expired(&mut store).await, Ok(1));
+
+        store
+            .touch_session(&first.id, first.created_at + 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_session(&first.id).await.unwrap().last_seen_at,
+            first.created_at + 60
+        );
+
+        assert_eq!(
+            store.remove_session(&email, &other.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        store.remove_session(&email, &first.id).await.unwrap();
+        assert_eq!(store.get_sessions(&email).await, Ok(vec![]));
+
+        store.remove_sessions(&other.email).await.unwrap();
+        assert_eq!(
+            store.get_session(&other.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let dir = TempDir::new().unwrap();
+        let mut store = store(&dir).await;
+        let email = Email::parse("user@example.com").unwrap();
+        let new_email = Email::parse("new@example.com").unwrap();
+        let taken = Email::parse("taken@example.com").unwrap();
+        let password = Password::parse("password").unwrap();
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(
+            UserStore::change_email(&mut store, &email, &new_email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        for email in [&email, &taken] {
+            store
+                .add_user(NewUser::new(email.clone(), password.clone(), false))
+                .await
+                .unwrap();
+        }
+        store.set_recovery_codes(&email, &codes).await.unwrap();
+        let session = Session::new(SessionId::default(), email.clone(), None, None);
+        store.add_session(session.clone()).await.unwrap();
+        store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            UserStore::change_email(&mut store, &email, &taken).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+        UserStore::change_email(&mut store, &email, &new_email)
+            .await
+            .unwrap();
+        SessionStore::change_email(&mut store, &email, &new_email)
+            .await
+            .unwrap();
+        TwoFACodeStore::change_email(&mut store, &email, &new_email)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert!(store.validate_user(&new_email, &password).await.is_ok());
+        // Recovery codes follow the user
+        assert_eq!(
+            store.use_recovery_code(&new_email, &codes[0]).await,
+            Ok(true)
+        );
+        assert_eq!(
+            store.get_session(&session.id).await.unwrap().email,
+            new_email
+        );
+        assert!(store.get_code(&new_email).await.is_ok());
+        assert_eq!(
+            store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+}
@@ -1,15 +1,39 @@
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::Validation;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use uuid::Uuid;
 
-use crate::domain::Email;
+use crate::domain::data_stores::{
+    RefreshTokenEntry, RefreshTokenFamilyId, RefreshTokenStore, RefreshTokenStoreError, Session,
+    SessionId, SessionStoreError, UserStoreError,
+};
+use crate::domain::errors::AuthAPIError;
+use crate::domain::user::{User, UserId};
+use crate::domain::{Email, RefreshToken};
+use crate::{AppState, BannedStoreType, RefreshTokenStoreType, UserStoreType};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::client_info::ClientInfo;
+use super::constants::{
+    EMAIL_CHANGE_AUDIENCE, EMAIL_CHANGE_TOKEN_TTL_SECONDS, EMAIL_VERIFICATION_AUDIENCE,
+    EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER,
+    PASSWORD_RESET_AUDIENCE, PASSWORD_RESET_TOKEN_TTL_SECONDS, REFRESH_COOKIE_NAME,
+};
+use super::signing_key::keyring;
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token for user `user_id`'s session `session_id`
+pub fn generate_auth_cookie(
+    user_id: &UserId,
+    session_id: &SessionId,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(user_id, session_id)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -24,6 +48,84 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Issue a new refresh token in `family_id` and put it in its own cookie.
+// A login starts a new family; a rotation at `/refresh` continues the old one.
+pub async fn generate_refresh_cookie(
+    refresh_token_store: &mut (dyn RefreshTokenStore + Send + Sync),
+    email: &Email,
+    family_id: RefreshTokenFamilyId,
+) -> Result<Cookie<'static>, RefreshTokenStoreError> {
+    let token = RefreshToken::generate();
+    refresh_token_store
+        .add_token(&token, RefreshTokenEntry::new(email.clone(), family_id))
+        .await?;
+
+    Ok(create_refresh_cookie(token))
+}
+
+// The refresh token is only ever sent by the browser itself, never by cross-site requests
+fn create_refresh_cookie(token: RefreshToken) -> Cookie<'static> {
+    Cookie::build((REFRESH_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .build()
+}
+
+// Log the user in: a new session, a short-lived JWT for it and a refresh token starting its family
+pub async fn add_login_cookies(
+    jar: CookieJar,
+    app_state: &AppState,
+    email: &Email,
+    client: ClientInfo,
+) -> Result<CookieJar, AuthAPIError> {
+    let user = app_state
+        .user_store
+        .read()
+        .await
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // Every way of logging in ends here, so none of them gets around the requirement
+    check_email_verified(&user, app_state)?;
+
+    let session = Session::new(
+        RefreshTokenFamilyId::default(),
+        email.clone(),
+        client.ip,
+        client.user_agent,
+    );
+
+    let auth_cookie =
+        generate_auth_cookie(&user.id, &session.id).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let refresh_cookie = generate_refresh_cookie(
+        &mut *app_state.refresh_token_store.write().await,
+        email,
+        session.id.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    app_state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(jar.add(auth_cookie).add(refresh_cookie))
+}
+
+// Refuse to log in a user who has yet to verify their address, where that is required
+pub fn check_email_verified(user: &User, app_state: &AppState) -> Result<(), AuthAPIError> {
+    if app_state.require_verified_email && !user.email_verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -33,62 +135,559 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
-// Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+// Create JWT auth token. Its `sub` is the user's id, which, unlike their email, never changes.
+fn generate_auth_token(
+    user_id: &UserId,
+    session_id: &SessionId,
+) -> Result<String, GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
+    // Cast the timestamps to usize, which is what Claims expects
     let exp: usize = exp
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let sub = email.as_ref().to_owned();
+    let sub = user_id.as_ref().to_owned();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub,
+        exp,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        iat,
+        nbf: iat,
+        iat_ms: Some(now.timestamp_millis()),
+        // Tells apart tokens issued to the same user within the same second
+        jti: Uuid::new_v4().to_string(),
+        sid: session_id.as_ref().to_owned(),
+    };
 
     create_token(&claims).map_err(GenerateTokenError::TokenError)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-        .map(|data| data.claims)
+// Check if JWT auth token is valid by verifying it against the keyring, and that its
+// user has not since logged out everywhere. Returns the user along with the claims.
+pub async fn validate_token(
+    token: &str,
+    user_store: &UserStoreType,
+) -> Result<(Claims, User), AuthAPIError> {
+    let claims = keyring()
+        .verify::<Claims>(token, &validation(), TOKEN_TTL_SECONDS)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let user = match token_user(&claims.sub, user_store).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+    if !user.accepts_token_issued_at(claims.issued_at_ms()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok((claims, user))
+}
+
+// The user an access token's `sub` names. Tokens issued before users had ids name
+// them by email instead; these are still looked up that way, which only matters
+// until they expire, TOKEN_TTL_SECONDS after the upgrade.
+async fn token_user(sub: &str, user_store: &UserStoreType) -> Result<User, UserStoreError> {
+    let user_store = user_store.read().await;
+    if let Ok(id) = UserId::parse(sub.to_owned()) {
+        return user_store.get_user_by_id(&id).await;
+    }
+
+    match Email::parse(sub) {
+        Ok(email) => user_store.get_user(&email).await,
+        Err(_) => Err(UserStoreError::UserNotFound),
+    }
+}
+
+// Only tokens this issuer made for this audience are accepted, and not before their `nbf`
+fn validation() -> Validation {
+    let mut validation = Validation::default();
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[JWT_AUDIENCE.as_str()]);
+    validation.validate_nbf = true;
+    validation
+}
+
+// Authenticate a request by its JWT cookie, rejecting tokens that have been banned by a logout
+pub async fn validate_auth_cookie(
+    jar: &CookieJar,
+    app_state: &AppState,
+) -> Result<(Claims, User), AuthAPIError> {
+    let token = jar
+        .get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value();
+
+    validate_unbanned_token(token, app_state).await
+}
+
+// Check the token as `validate_token` does, and that neither it has been banned
+// nor its session ended since
+pub async fn validate_unbanned_token(
+    token: &str,
+    app_state: &AppState,
+) -> Result<(Claims, User), AuthAPIError> {
+    let (claims, user) = validate_token(token, &app_state.user_store).await?;
+
+    let banned = app_state
+        .banned_token_store
+        .read()
+        .await
+        .contains(&claims.jti)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if banned {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let session_id =
+        SessionId::parse(claims.sid.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    match app_state
+        .session_store
+        .read()
+        .await
+        .get_session(&session_id)
+        .await
+    {
+        // Sessions move along with the user when they change their address
+        Ok(session) if session.email == user.email => Ok((claims, user)),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+// What a token emailed to the user lets its holder do. These tokens are signed like
+// access tokens, but each purpose has an audience of its own, so a token is accepted
+// neither for another purpose nor as an access token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    EmailChange,
+}
+
+impl EmailTokenPurpose {
+    const ALL: [EmailTokenPurpose; 3] = [
+        EmailTokenPurpose::PasswordReset,
+        EmailTokenPurpose::EmailVerification,
+        EmailTokenPurpose::EmailChange,
+    ];
+
+    fn audience(self) -> &'static str {
+        match self {
+            EmailTokenPurpose::PasswordReset => PASSWORD_RESET_AUDIENCE,
+            EmailTokenPurpose::EmailVerification => EMAIL_VERIFICATION_AUDIENCE,
+            EmailTokenPurpose::EmailChange => EMAIL_CHANGE_AUDIENCE,
+        }
+    }
+
+    fn ttl_seconds(self) -> u64 {
+        match self {
+            EmailTokenPurpose::PasswordReset => *PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            EmailTokenPurpose::EmailVerification => *EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            EmailTokenPurpose::EmailChange => *EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+        }
+    }
+
+    // As for access tokens, but for this purpose's audience
+    fn validation(self) -> Validation {
+        let mut validation = validation();
+        validation.set_audience(&[self.audience()]);
+        validation
+    }
+
+    // Check the token's signature, expiry and audience. Keys replaced longer ago than
+    // these tokens live are not accepted.
+    fn verify(self, token: &str) -> Result<EmailTokenClaims, jsonwebtoken::errors::Error> {
+        keyring().verify(token, &self.validation(), self.ttl_seconds() as i64)
+    }
+}
+
+// How long a token signed now may stay valid, whatever its kind. A key replaced in the
+// keyring has to verify tokens for this long.
+pub fn longest_token_ttl_seconds() -> i64 {
+    EmailTokenPurpose::ALL
+        .iter()
+        .map(|purpose| purpose.ttl_seconds() as i64)
+        .fold(TOKEN_TTL_SECONDS, i64::max)
+}
+
+// Create a token to email to `email`, for use with the link it is sent in
+pub fn generate_email_token(
+    email: &Email,
+    purpose: EmailTokenPurpose,
+) -> Result<String, GenerateTokenError> {
+    let claims = email_token_claims(email, purpose)?;
+
+    keyring()
+        .sign(&claims)
+        .map_err(GenerateTokenError::TokenError)
+}
+
+fn email_token_claims(
+    email: &Email,
+    purpose: EmailTokenPurpose,
+) -> Result<EmailTokenClaims, GenerateTokenError> {
+    let now = Utc::now();
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    Ok(EmailTokenClaims {
+        sub: email.as_ref().to_owned(),
+        exp: iat + purpose.ttl_seconds() as usize,
+        iss: JWT_ISSUER.to_owned(),
+        aud: purpose.audience().to_owned(),
+        iat,
+        nbf: iat,
+        iat_ms: Some(now.timestamp_millis()),
+        jti: Uuid::new_v4().to_string(),
+        new_email: None,
+    })
+}
+
+// Check an emailed token: its signature, purpose and expiry, that its user still
+// exists, and that it has not been used yet. Returns the user along with the claims.
+// Only `consume_email_token` settles whether the token is still unused, though.
+pub async fn validate_email_token(
+    token: &str,
+    purpose: EmailTokenPurpose,
+    app_state: &AppState,
+) -> Result<(EmailTokenClaims, User), AuthAPIError> {
+    let claims = purpose
+        .verify(token)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    let user = match app_state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let used = app_state
+        .banned_token_store
+        .read()
+        .await
+        .contains(&claims.jti)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if used {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok((claims, user))
+}
+
+// Use up an emailed token, so it cannot be used again. Using it up and checking that
+// it was not used before are one step, so of two requests racing with the same token
+// only one gets through; whatever the token allows must only be done once this succeeds.
+pub async fn consume_email_token(
+    claims: EmailTokenClaims,
+    banned_token_store: &BannedStoreType,
+) -> Result<(), AuthAPIError> {
+    // Validation accepts a token for a little while past its `exp`, and so must the ban
+    let exp = claims.exp + validation().leeway as usize;
+    let consumed = banned_token_store
+        .write()
+        .await
+        .add(claims.jti, exp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if !consumed {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(())
+}
+
+// Create a token proving its holder may set a new password for `email`
+pub fn generate_password_reset_token(email: &Email) -> Result<String, GenerateTokenError> {
+    generate_email_token(email, EmailTokenPurpose::PasswordReset)
+}
+
+// Check a password reset token as `validate_email_token` does. It must also postdate
+// the user's last reset or logout-all.
+pub async fn validate_password_reset_token(
+    token: &str,
+    app_state: &AppState,
+) -> Result<EmailTokenClaims, AuthAPIError> {
+    let (claims, user) =
+        validate_email_token(token, EmailTokenPurpose::PasswordReset, app_state).await?;
+    if !user.accepts_token_issued_at(claims.issued_at_ms()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(claims)
+}
+
+// Create a token proving its holder may move the account of `email` to `new_email`.
+// It is sent to the new address, so following the link shows that address works.
+pub fn generate_email_change_token(
+    email: &Email,
+    new_email: &Email,
+) -> Result<String, GenerateTokenError> {
+    let mut claims = email_token_claims(email, EmailTokenPurpose::EmailChange)?;
+    claims.new_email = Some(new_email.as_ref().to_owned());
+
+    keyring()
+        .sign(&claims)
+        .map_err(GenerateTokenError::TokenError)
+}
+
+// Check an email change token as `validate_email_token` does. As with a reset token,
+// it must also postdate the user's last reset or logout-all. Returns the new address.
+pub async fn validate_email_change_token(
+    token: &str,
+    app_state: &AppState,
+) -> Result<(EmailTokenClaims, Email), AuthAPIError> {
+    let (claims, user) =
+        validate_email_token(token, EmailTokenPurpose::EmailChange, app_state).await?;
+    if !user.accepts_token_issued_at(claims.issued_at_ms()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let new_email = claims
+        .new_email
+        .as_deref()
+        .and_then(|new_email| Email::parse(new_email).ok())
+        .ok_or(AuthAPIError::InvalidToken)?;
+
+    Ok((claims, new_email))
+}
+
+// End every session of the user: each access and refresh token issued to them up to
+// now stops being accepted, including ones this service has never seen again
+pub async fn revoke_all_sessions(email: &Email, app_state: &AppState) -> Result<(), AuthAPIError> {
+    app_state
+        .user_store
+        .write()
+        .await
+        .set_tokens_valid_after(email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    app_state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// End one session of the user: its refresh tokens are revoked, and its access tokens
+// stop being accepted as their session is gone. A session of another user is not found.
+pub async fn revoke_session(
+    email: &Email,
+    session_id: &SessionId,
+    app_state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match app_state
+        .session_store
+        .write()
+        .await
+        .remove_session(email, session_id)
+        .await
+    {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => return Err(AuthAPIError::SessionNotFound),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    app_state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(session_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Ban an access token for the rest of its lifetime, so it is refused although its signature checks out
+pub async fn revoke_access_token(
+    claims: Claims,
+    banned_token_store: &BannedStoreType,
+) -> Result<(), AuthAPIError> {
+    banned_token_store
+        .write()
+        .await
+        .add(claims.jti, claims.exp)
+        .await
+        .map(|_| ())
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Revoke a refresh token along with every token rotated from the same login.
+// Returns false if the store does not know the token.
+pub async fn revoke_refresh_token(
+    token: &RefreshToken,
+    refresh_token_store: &RefreshTokenStoreType,
+) -> Result<bool, AuthAPIError> {
+    let mut refresh_token_store = refresh_token_store.write().await;
+
+    let entry = match refresh_token_store.use_token(token).await {
+        Ok(entry) => entry,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Ok(false),
+        Err(RefreshTokenStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+    refresh_token_store
+        .revoke_family(&entry.family_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(true)
+}
+
+// Authenticate an `/admin/...` request by its `Authorization: Bearer` header.
+// Without a configured admin token every request is refused.
+pub fn validate_admin_token(
+    headers: &HeaderMap,
+    admin_token: Option<&str>,
+) -> Result<(), AuthAPIError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .ok_or(AuthAPIError::MissingToken)?
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthAPIError::InvalidToken)?;
+    let admin_token = admin_token.ok_or(AuthAPIError::InvalidToken)?;
+
+    // Comparing digests keeps the time taken from depending on how much of the token matched
+    if Sha256::digest(token.as_bytes()) != Sha256::digest(admin_token.as_bytes()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(())
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
+// Authenticate a trusted service by its `Authorization: Basic` header, as RFC 6749
+// section 2.3.1 describes. Without any configured client every request is refused.
+pub fn validate_client_credentials(
+    headers: &HeaderMap,
+    clients: &HashMap<String, String>,
+) -> Result<(), AuthAPIError> {
+    let credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| STANDARD.decode(value).ok())
+        .and_then(|value| String::from_utf8(value).ok())
+        .ok_or(AuthAPIError::InvalidClient)?;
+    let (client_id, secret) = credentials
+        .split_once(':')
+        .ok_or(AuthAPIError::InvalidClient)?;
+    let expected = clients.get(client_id).ok_or(AuthAPIError::InvalidClient)?;
+
+    if Sha256::digest(secret.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+        return Err(AuthAPIError::InvalidClient);
+    }
+
+    Ok(())
+}
+
+// Create JWT auth token by signing the claims with the keyring's current key
 fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+    keyring().sign(claims)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
+    // The user's id, or their email in tokens issued before users had ids
     pub sub: String,
     pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    pub nbf: usize,
+    // `iat` in milliseconds, so a token issued in the same second as a logout-all can
+    // still be told apart from the ones it revoked. Missing from older tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,
+    pub jti: String,
+    // The session, i.e. the login, the token was issued to
+    pub sid: String,
+}
+
+impl Claims {
+    // Older tokens count as issued at the start of their `iat` second
+    pub fn issued_at_ms(&self) -> i64 {
+        self.iat_ms.unwrap_or(self.iat as i64 * 1000)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailTokenClaims {
+    // The address the token was emailed to, so it stops working if the user moves elsewhere
+    pub sub: String,
+    pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    pub nbf: usize,
+    // As in `Claims`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,
+    pub jti: String,
+    // Where the account moves to, for an email change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_email: Option<String>,
+}
+
+impl EmailTokenClaims {
+    pub fn issued_at_ms(&self) -> i64 {
+        self.iat_ms.unwrap_or(self.iat as i64 * 1000)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::data_stores::UserStore;
+    use crate::domain::user::NewUser;
+    use crate::domain::Password;
+    use crate::utils::signing_key::{Keyring, SigningKey};
+    use crate::{HashMapRefreshTokenStore, HashMapUserStore};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    // A user store that knows the user `email`, along with the id it gave them
+    async fn user_store_with(email: &Email) -> (UserStoreType, UserId) {
+        let mut store = HashMapUserStore::new();
+        store
+            .add_user(NewUser::new(
+                email.clone(),
+                Password::parse("password").unwrap(),
+                false,
+            ))
+            .await
+            .unwrap();
+        let id = store.get_user(email).await.unwrap().id;
+
+        (Arc::new(RwLock::new(store)), id)
+    }
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&UserId::default(), &SessionId::default()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -108,18 +707,89 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_generate_auth_token() {
+    async fn test_generate_refresh_cookie() {
+        let mut store = HashMapRefreshTokenStore::new();
         let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let family_id = RefreshTokenFamilyId::default();
+
+        let cookie = generate_refresh_cookie(&mut store, &email, family_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+
+        let token = RefreshToken::parse(cookie.value().to_owned()).unwrap();
+        let entry = store.use_token(&token).await.unwrap();
+        assert_eq!(entry.email, email);
+        assert_eq!(entry.family_id, family_id);
+    }
+
+    #[test]
+    fn test_validate_admin_token() {
+        let bearer = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(validate_admin_token(&bearer("Bearer admin"), Some("admin")).is_ok());
+        assert!(matches!(
+            validate_admin_token(&HeaderMap::new(), Some("admin")),
+            Err(AuthAPIError::MissingToken)
+        ));
+        for (headers, admin_token) in [
+            (bearer("Bearer wrong"), Some("admin")),
+            (bearer("admin"), Some("admin")),
+            (bearer("Bearer admin"), None),
+        ] {
+            assert!(matches!(
+                validate_admin_token(&headers, admin_token),
+                Err(AuthAPIError::InvalidToken)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_client_credentials() {
+        let clients = HashMap::from([("api".to_owned(), "secret".to_owned())]);
+        let basic = |credentials: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("Basic {}", STANDARD.encode(credentials));
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(validate_client_credentials(&basic("api:secret"), &clients).is_ok());
+        for (headers, clients) in [
+            (basic("api:wrong"), &clients),
+            (basic("other:secret"), &clients),
+            (basic("api"), &clients),
+            (HeaderMap::new(), &clients),
+            (basic("api:secret"), &HashMap::new()),
+        ] {
+            assert!(matches!(
+                validate_client_credentials(&headers, clients),
+                Err(AuthAPIError::InvalidClient)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token() {
+        let result = generate_auth_token(&UserId::default(), &SessionId::default()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let result = validate_token(&token).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let (user_store, id) = user_store_with(&email).await;
+        let token = generate_auth_token(&id, &SessionId::default()).unwrap();
+        let (result, user) = validate_token(&token, &user_store).await.unwrap();
+        assert_eq!(result.sub, id.as_ref());
+        assert_eq!(user.email, email);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -129,10 +799,193 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_generate_auth_token_sets_registered_claims() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, id) = user_store_with(&email).await;
+        let session_id = SessionId::default();
+        let (first, _) =
+            validate_token(&generate_auth_token(&id, &session_id).unwrap(), &user_store)
+                .await
+                .unwrap();
+        let (second, _) =
+            validate_token(&generate_auth_token(&id, &session_id).unwrap(), &user_store)
+                .await
+                .unwrap();
+
+        assert_eq!(first.iss, *JWT_ISSUER);
+        assert_eq!(first.aud, *JWT_AUDIENCE);
+        assert_eq!(first.nbf, first.iat);
+        assert_eq!(first.exp, first.iat + TOKEN_TTL_SECONDS as usize);
+        assert_ne!(first.jti, second.jti);
+        assert_eq!(first.sid, session_id.as_ref());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_other_issuer_or_audience() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, id) = user_store_with(&email).await;
+        let (claims, _) = validate_token(
+            &generate_auth_token(&id, &SessionId::default()).unwrap(),
+            &user_store,
+        )
+        .await
+        .unwrap();
+
+        let other_issuer = Claims {
+            iss: "other-issuer".to_owned(),
+            ..claims.clone()
+        };
+        let other_audience = Claims {
+            aud: "other-app".to_owned(),
+            ..claims.clone()
+        };
+        let not_yet_valid = Claims {
+            nbf: claims.iat + 3600,
+            ..claims
+        };
+
+        for claims in [other_issuer, other_audience, not_yet_valid] {
+            let token = create_token(&claims).unwrap();
+            assert!(validate_token(&token, &user_store).await.is_err());
+        }
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
+        let (user_store, _) = user_store_with(&Email::parse("test@example.com").unwrap()).await;
         let token = "invalid_token".to_owned();
-        let result = validate_token(&token).await;
+        let result = validate_token(&token, &user_store).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_tokens_issued_before_logout_all() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, id) = user_store_with(&email).await;
+        let token = generate_auth_token(&id, &SessionId::default()).unwrap();
+        let (claims, _) = validate_token(&token, &user_store).await.unwrap();
+
+        user_store
+            .write()
+            .await
+            .set_tokens_valid_after(&email, claims.issued_at_ms() - 1)
+            .await
+            .unwrap();
+        assert!(validate_token(&token, &user_store).await.is_ok());
+
+        user_store
+            .write()
+            .await
+            .set_tokens_valid_after(&email, claims.issued_at_ms())
+            .await
+            .unwrap();
+        assert!(matches!(
+            validate_token(&token, &user_store).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_rotated_key_verifies_verification_tokens_until_they_expire() {
+        let purpose = EmailTokenPurpose::EmailVerification;
+        let claims =
+            email_token_claims(&Email::parse("test@example.com").unwrap(), purpose).unwrap();
+        let mut keyring = Keyring::new(SigningKey::hmac(b"old secret", None));
+        let token = keyring.sign(&claims).unwrap();
+
+        keyring
+            .rotate(SigningKey::hmac(b"new secret", None))
+            .unwrap();
+        // Access tokens signed with the old key have all expired by now
+        keyring.advance(TOKEN_TTL_SECONDS);
+        assert!(keyring
+            .verify::<EmailTokenClaims>(&token, &purpose.validation(), purpose.ttl_seconds() as i64)
+            .is_ok());
+
+        keyring.advance(purpose.ttl_seconds() as i64 - TOKEN_TTL_SECONDS);
+        assert!(keyring
+            .verify::<EmailTokenClaims>(&token, &purpose.validation(), purpose.ttl_seconds() as i64)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_token_is_not_an_access_token() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, _) = user_store_with(&email).await;
+        let token = generate_password_reset_token(&email).unwrap();
+
+        let claims = EmailTokenPurpose::PasswordReset.verify(&token).unwrap();
+        assert_eq!(claims.sub, email.as_ref());
+        assert_eq!(
+            claims.exp,
+            claims.iat + *PASSWORD_RESET_TOKEN_TTL_SECONDS as usize
+        );
+
+        assert!(matches!(
+            validate_token(&token, &user_store).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+        assert!(EmailTokenPurpose::EmailVerification.verify(&token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_unknown_user() {
+        let (user_store, _) = user_store_with(&Email::parse("other@example.com").unwrap()).await;
+        let token = generate_auth_token(&UserId::default(), &SessionId::default()).unwrap();
+
+        assert!(matches!(
+            validate_token(&token, &user_store).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_follows_user_to_new_email() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, id) = user_store_with(&email).await;
+        let token = generate_auth_token(&id, &SessionId::default()).unwrap();
+
+        let new_email = Email::parse("new@example.com").unwrap();
+        user_store
+            .write()
+            .await
+            .change_email(&email, &new_email)
+            .await
+            .unwrap();
+
+        let (_, user) = validate_token(&token, &user_store).await.unwrap();
+        assert_eq!(user.email, new_email);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_accepts_tokens_naming_email() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, id) = user_store_with(&email).await;
+        let (claims, _) = validate_token(
+            &generate_auth_token(&id, &SessionId::default()).unwrap(),
+            &user_store,
+        )
+        .await
+        .unwrap();
+
+        // As issued before users had ids
+        let legacy = create_token(&Claims {
+            sub: email.as_ref().to_owned(),
+            ..claims.clone()
+        })
+        .unwrap();
+        let (_, user) = validate_token(&legacy, &user_store).await.unwrap();
+        assert_eq!(user.id, id);
+
+        let unknown = create_token(&Claims {
+            sub: "other@example.com".to_owned(),
+            ..claims
+        })
+        .unwrap();
+        assert!(matches!(
+            validate_token(&unknown, &user_store).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
 }
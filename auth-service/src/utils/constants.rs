@@ -1,10 +1,51 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
+use std::collections::HashMap;
 use std::env as std_env;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
+    pub static ref DATABASE_URL: String = set_database_url();
+    pub static ref USER_STORE: String = set_user_store();
+    pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref TOKEN_STORE: String = set_token_store();
+    pub static ref SQLITE_PATH: String = set_sqlite_path();
+    pub static ref TWO_FA_CODE_TTL_SECONDS: u64 = set_two_fa_code_ttl();
+    pub static ref TWO_FA_MAX_ATTEMPTS: u32 = set_two_fa_max_attempts();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_RP_ORIGIN: String = set_webauthn_rp_origin();
+    pub static ref REFRESH_TOKEN_TTL_SECONDS: u64 = set_refresh_token_ttl();
+    pub static ref JWT_ALGORITHM: String = set_jwt_algorithm();
+    pub static ref JWT_PRIVATE_KEY_PATH: Option<String> = set_jwt_private_key_path();
+    pub static ref JWT_KEY_ID: Option<String> = set_jwt_key_id();
+    pub static ref JWT_SECRET_PATH: Option<String> = set_jwt_secret_path();
+    pub static ref ADMIN_TOKEN: Option<String> = set_admin_token();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
+    pub static ref SWEEP_INTERVAL_SECONDS: u64 = set_sweep_interval();
+    pub static ref CLIENT_CREDENTIALS: HashMap<String, String> = set_client_credentials();
+    pub static ref PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = set_password_reset_token_ttl();
+    pub static ref PASSWORD_RESET_URL: String = set_password_reset_url();
+    pub static ref EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = set_email_verification_token_ttl();
+    pub static ref EMAIL_VERIFICATION_URL: String = set_email_verification_url();
+    pub static ref EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS: u64 = set_email_verification_resend_interval();
+    pub static ref REQUIRE_VERIFIED_EMAIL: bool = set_require_verified_email();
+    pub static ref EMAIL_CHANGE_TOKEN_TTL_SECONDS: u64 = set_email_change_token_ttl();
+    pub static ref EMAIL_CHANGE_URL: String = set_email_change_url();
+    pub static ref EMAIL_CLIENT: String = set_email_client();
+    pub static ref EMAIL_SENDER: String = set_email_sender();
+    pub static ref SMTP_HOST: String = set_smtp_host();
+    pub static ref SMTP_PORT: Option<u16> = set_smtp_port();
+    pub static ref SMTP_TLS: String = set_smtp_tls();
+    pub static ref SMTP_USERNAME: Option<String> = set_smtp_username();
+    pub static ref SMTP_PASSWORD: Option<String> = set_smtp_password();
+    pub static ref SMTP_TIMEOUT_SECONDS: u64 = set_smtp_timeout();
+    pub static ref SMTP_POOL_SIZE: u32 = set_smtp_pool_size();
+    pub static ref EMAIL_API_URL: String = set_email_api_url();
+    pub static ref EMAIL_API_TOKEN: String = set_email_api_token();
+    pub static ref EMAIL_API_TOKEN_HEADER: String = set_email_api_token_header();
+    pub static ref EMAIL_API_TIMEOUT_SECONDS: u64 = set_email_api_timeout();
 }
 
 
@@ -17,8 +58,375 @@ fn set_token() -> String {
     secret
 }
 
+fn set_database_url() -> String {
+    dotenv().ok(); // Load environment variables
+    let url = std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set.");
+    if url.is_empty() {
+        panic!("DATABASE_URL must not be empty.");
+    }
+    url
+}
+
+// Which UserStore backend to run with: "postgres" (default), "sqlite" or "memory"
+fn set_user_store() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::USER_STORE_ENV_VAR).unwrap_or_else(|_| "postgres".to_owned())
+}
+
+fn set_redis_host() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
+}
+
+// Which BannedTokenStore and TwoFACodeStore backend to run with: "redis" (default), "sqlite" or "memory"
+fn set_token_store() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::TOKEN_STORE_ENV_VAR).unwrap_or_else(|_| "redis".to_owned())
+}
+
+fn set_sqlite_path() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SQLITE_PATH_ENV_VAR).unwrap_or(DEFAULT_SQLITE_PATH.to_owned())
+}
+
+// How long an emailed 2FA code stays usable
+fn set_two_fa_code_ttl() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::TWO_FA_CODE_TTL_SECONDS_ENV_VAR)
+        .map(|ttl| ttl.parse().expect("TWO_FA_CODE_TTL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_TWO_FA_CODE_TTL_SECONDS)
+}
+
+// How many wrong 2FA codes are tolerated before the login attempt is thrown away
+fn set_two_fa_max_attempts() -> u32 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::TWO_FA_MAX_ATTEMPTS_ENV_VAR)
+        .map(|max| max.parse().expect("TWO_FA_MAX_ATTEMPTS must be a number."))
+        .unwrap_or(DEFAULT_TWO_FA_MAX_ATTEMPTS)
+}
+
+// The domain passkeys are bound to; it must be the origin's host or a parent domain of it
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+// The origin the browser reports during passkey ceremonies, i.e. where the login UI is served from
+fn set_webauthn_rp_origin() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::WEBAUTHN_RP_ORIGIN_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ORIGIN.to_owned())
+}
+
+// How long a refresh token can be traded for a new JWT; each rotation starts the clock again
+fn set_refresh_token_ttl() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::REFRESH_TOKEN_TTL_SECONDS_ENV_VAR)
+        .map(|ttl| ttl.parse().expect("REFRESH_TOKEN_TTL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_REFRESH_TOKEN_TTL_SECONDS)
+}
+
+// How access tokens are signed: "HS256" (default) with JWT_SECRET, or "RS256" / "EdDSA"
+// with the key pair in JWT_PRIVATE_KEY_PATH
+fn set_jwt_algorithm() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_ALGORITHM_ENV_VAR).unwrap_or(DEFAULT_JWT_ALGORITHM.to_owned())
+}
+
+// A PEM file holding the RSA or Ed25519 private key; the public key is derived from it.
+// The file is read again whenever the keyring is reloaded.
+fn set_jwt_private_key_path() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_PRIVATE_KEY_PATH_ENV_VAR).ok()
+}
+
+// The `kid` put in every token header. Left unset, a key is named after a hash of it,
+// so replacing the key file also changes the kid.
+fn set_jwt_key_id() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_KEY_ID_ENV_VAR).ok()
+}
+
+// A file holding the HS256 secret instead of JWT_SECRET, so the secret can be rotated without a restart
+fn set_jwt_secret_path() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_SECRET_PATH_ENV_VAR).ok()
+}
+
+// Bearer token for the `/admin/...` endpoints; they refuse every request while it is unset
+fn set_admin_token() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::ADMIN_TOKEN_ENV_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+// The `iss` put in every token; only tokens naming this issuer are accepted
+fn set_jwt_issuer() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_ISSUER_ENV_VAR).unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+// The `aud` put in every token, naming the app the tokens are for. A token issued for
+// another app, even by the same issuer, is refused.
+fn set_jwt_audience() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR).unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
+// How often expired banned tokens, 2FA codes, passkey ceremonies and refresh tokens are purged
+fn set_sweep_interval() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SWEEP_INTERVAL_SECONDS_ENV_VAR)
+        .ok()
+        .map(|interval| {
+            interval
+                .parse()
+                .ok()
+                .filter(|interval| *interval > 0)
+                .expect("SWEEP_INTERVAL_SECONDS must be a positive number of seconds.")
+        })
+        .unwrap_or(DEFAULT_SWEEP_INTERVAL_SECONDS)
+}
+
+// Trusted services allowed to call `/introspect` and `/revoke`, as comma-separated `client_id:secret` pairs
+fn set_client_credentials() -> HashMap<String, String> {
+    dotenv().ok(); // Load environment variables
+    let Ok(clients) = std_env::var(env::CLIENT_CREDENTIALS_ENV_VAR) else {
+        return HashMap::new();
+    };
+
+    clients
+        .split(',')
+        .map(str::trim)
+        .filter(|client| !client.is_empty())
+        .map(|client| {
+            client
+                .split_once(':')
+                .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+                .map(|(id, secret)| (id.to_owned(), secret.to_owned()))
+                .expect("CLIENT_CREDENTIALS must be a list of client_id:secret pairs.")
+        })
+        .collect()
+}
+
+// How long the link emailed by `/password/forgot` can be used
+fn set_password_reset_token_ttl() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::PASSWORD_RESET_TOKEN_TTL_SECONDS_ENV_VAR)
+        .map(|ttl| ttl.parse().expect("PASSWORD_RESET_TOKEN_TTL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS)
+}
+
+// The page of the login UI that asks for the new password; the reset token is appended as `?token=`
+fn set_password_reset_url() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::PASSWORD_RESET_URL_ENV_VAR).unwrap_or(DEFAULT_PASSWORD_RESET_URL.to_owned())
+}
+
+// How long the link emailed to confirm a new account's address can be used
+fn set_email_verification_token_ttl() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS_ENV_VAR)
+        .map(|ttl| ttl.parse().expect("EMAIL_VERIFICATION_TOKEN_TTL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)
+}
+
+// The page of the login UI that confirms an address; the token is appended as `?token=`
+fn set_email_verification_url() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_VERIFICATION_URL_ENV_VAR).unwrap_or(DEFAULT_EMAIL_VERIFICATION_URL.to_owned())
+}
+
+// The least time between two verification emails to the same user
+fn set_email_verification_resend_interval() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS_ENV_VAR)
+        .map(|interval| interval.parse().expect("EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS)
+}
+
+// Whether users must verify their address before they can log in. Off by default, so
+// accounts keep working while their users catch up on the verification email.
+fn set_require_verified_email() -> bool {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::REQUIRE_VERIFIED_EMAIL_ENV_VAR)
+        .map(|required| required.parse().expect("REQUIRE_VERIFIED_EMAIL must be true or false."))
+        .unwrap_or(false)
+}
+
+// How long the link emailed to a user's new address to confirm the change can be used
+fn set_email_change_token_ttl() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_CHANGE_TOKEN_TTL_SECONDS_ENV_VAR)
+        .map(|ttl| ttl.parse().expect("EMAIL_CHANGE_TOKEN_TTL_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_EMAIL_CHANGE_TOKEN_TTL_SECONDS)
+}
+
+// The page of the login UI that confirms a new address; the token is appended as `?token=`
+fn set_email_change_url() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_CHANGE_URL_ENV_VAR).unwrap_or(DEFAULT_EMAIL_CHANGE_URL.to_owned())
+}
+
+// How emails are sent: "mock" (default), which only prints them, "smtp", or "http"
+// for the REST API of an email provider
+fn set_email_client() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_CLIENT_ENV_VAR).unwrap_or_else(|_| "mock".to_owned())
+}
+
+// The `From` of every email sent, either a bare address or `Name <address>`
+fn set_email_sender() -> String {
+    dotenv().ok(); // Load environment variables
+    let sender = std_env::var(env::EMAIL_SENDER_ENV_VAR).expect("EMAIL_SENDER must be set.");
+    if sender.is_empty() {
+        panic!("EMAIL_SENDER must not be empty.");
+    }
+    sender
+}
+
+fn set_smtp_host() -> String {
+    dotenv().ok(); // Load environment variables
+    let host = std_env::var(env::SMTP_HOST_ENV_VAR).expect("SMTP_HOST must be set.");
+    if host.is_empty() {
+        panic!("SMTP_HOST must not be empty.");
+    }
+    host
+}
+
+// Left unset, the usual port for SMTP_TLS is used: 587, 465, or 25 without TLS
+fn set_smtp_port() -> Option<u16> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_PORT_ENV_VAR)
+        .ok()
+        .map(|port| port.parse().expect("SMTP_PORT must be a port number."))
+}
+
+// How the connection to the SMTP server is secured: "starttls" (default), "tls" for
+// implicit TLS, or "none", which is only fit for a local mail sink
+fn set_smtp_tls() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_TLS_ENV_VAR).unwrap_or_else(|_| "starttls".to_owned())
+}
+
+// The SMTP server is logged in to only when both the username and password are set
+fn set_smtp_username() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_USERNAME_ENV_VAR)
+        .ok()
+        .filter(|username| !username.is_empty())
+}
+
+fn set_smtp_password() -> Option<String> {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_PASSWORD_ENV_VAR)
+        .ok()
+        .filter(|password| !password.is_empty())
+}
+
+// How long sending one email may take, connecting to the SMTP server included
+fn set_smtp_timeout() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_TIMEOUT_SECONDS_ENV_VAR)
+        .map(|timeout| timeout.parse().expect("SMTP_TIMEOUT_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_SMTP_TIMEOUT_SECONDS)
+}
+
+// How many connections to the SMTP server are kept open for reuse
+fn set_smtp_pool_size() -> u32 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::SMTP_POOL_SIZE_ENV_VAR)
+        .ok()
+        .map(|size| {
+            size.parse()
+                .ok()
+                .filter(|size| *size > 0)
+                .expect("SMTP_POOL_SIZE must be a positive number.")
+        })
+        .unwrap_or(DEFAULT_SMTP_POOL_SIZE)
+}
+
+// Where emails are posted to when EMAIL_CLIENT is "http",
+// e.g. https://api.postmarkapp.com/email
+fn set_email_api_url() -> String {
+    dotenv().ok(); // Load environment variables
+    let url = std_env::var(env::EMAIL_API_URL_ENV_VAR).expect("EMAIL_API_URL must be set.");
+    if url.is_empty() {
+        panic!("EMAIL_API_URL must not be empty.");
+    }
+    url
+}
+
+fn set_email_api_token() -> String {
+    dotenv().ok(); // Load environment variables
+    let token = std_env::var(env::EMAIL_API_TOKEN_ENV_VAR).expect("EMAIL_API_TOKEN must be set.");
+    if token.is_empty() {
+        panic!("EMAIL_API_TOKEN must not be empty.");
+    }
+    token
+}
+
+// The header the email provider expects the API token in
+fn set_email_api_token_header() -> String {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_API_TOKEN_HEADER_ENV_VAR)
+        .unwrap_or(DEFAULT_EMAIL_API_TOKEN_HEADER.to_owned())
+}
+
+// How long one request to the email provider may take, connecting included
+fn set_email_api_timeout() -> u64 {
+    dotenv().ok(); // Load environment variables
+    std_env::var(env::EMAIL_API_TIMEOUT_SECONDS_ENV_VAR)
+        .map(|timeout| {
+            timeout
+                .parse()
+                .expect("EMAIL_API_TIMEOUT_SECONDS must be a number of seconds.")
+        })
+        .unwrap_or(DEFAULT_EMAIL_API_TIMEOUT_SECONDS)
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const USER_STORE_ENV_VAR: &str = "USER_STORE";
+    pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const TOKEN_STORE_ENV_VAR: &str = "TOKEN_STORE";
+    pub const SQLITE_PATH_ENV_VAR: &str = "SQLITE_PATH";
+    pub const TWO_FA_CODE_TTL_SECONDS_ENV_VAR: &str = "TWO_FA_CODE_TTL_SECONDS";
+    pub const TWO_FA_MAX_ATTEMPTS_ENV_VAR: &str = "TWO_FA_MAX_ATTEMPTS";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_RP_ORIGIN_ENV_VAR: &str = "WEBAUTHN_RP_ORIGIN";
+    pub const REFRESH_TOKEN_TTL_SECONDS_ENV_VAR: &str = "REFRESH_TOKEN_TTL_SECONDS";
+    pub const JWT_ALGORITHM_ENV_VAR: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_KEY_ID_ENV_VAR: &str = "JWT_KEY_ID";
+    pub const JWT_SECRET_PATH_ENV_VAR: &str = "JWT_SECRET_PATH";
+    pub const ADMIN_TOKEN_ENV_VAR: &str = "ADMIN_TOKEN";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const SWEEP_INTERVAL_SECONDS_ENV_VAR: &str = "SWEEP_INTERVAL_SECONDS";
+    pub const CLIENT_CREDENTIALS_ENV_VAR: &str = "CLIENT_CREDENTIALS";
+    pub const PASSWORD_RESET_TOKEN_TTL_SECONDS_ENV_VAR: &str = "PASSWORD_RESET_TOKEN_TTL_SECONDS";
+    pub const PASSWORD_RESET_URL_ENV_VAR: &str = "PASSWORD_RESET_URL";
+    pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS_ENV_VAR: &str = "EMAIL_VERIFICATION_TOKEN_TTL_SECONDS";
+    pub const EMAIL_VERIFICATION_URL_ENV_VAR: &str = "EMAIL_VERIFICATION_URL";
+    pub const EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS_ENV_VAR: &str = "EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS";
+    pub const REQUIRE_VERIFIED_EMAIL_ENV_VAR: &str = "REQUIRE_VERIFIED_EMAIL";
+    pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS_ENV_VAR: &str = "EMAIL_CHANGE_TOKEN_TTL_SECONDS";
+    pub const EMAIL_CHANGE_URL_ENV_VAR: &str = "EMAIL_CHANGE_URL";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_TIMEOUT_SECONDS_ENV_VAR: &str = "SMTP_TIMEOUT_SECONDS";
+    pub const SMTP_POOL_SIZE_ENV_VAR: &str = "SMTP_POOL_SIZE";
+    pub const EMAIL_API_URL_ENV_VAR: &str = "EMAIL_API_URL";
+    pub const EMAIL_API_TOKEN_ENV_VAR: &str = "EMAIL_API_TOKEN";
+    pub const EMAIL_API_TOKEN_HEADER_ENV_VAR: &str = "EMAIL_API_TOKEN_HEADER";
+    pub const EMAIL_API_TIMEOUT_SECONDS_ENV_VAR: &str = "EMAIL_API_TIMEOUT_SECONDS";
 }
 
 pub mod prod {
@@ -28,4 +436,52 @@ pub mod prod {
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
 }
-pub const JWT_COOKIE_NAME: &str = "jwt";
\ No newline at end of file
+pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_SQLITE_PATH: &str = "auth-service.db";
+pub const DEFAULT_TWO_FA_CODE_TTL_SECONDS: u64 = 600; // 10 minutes
+pub const DEFAULT_TWO_FA_MAX_ATTEMPTS: u32 = 5;
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_RP_ORIGIN: &str = "http://localhost:3000";
+pub const DEFAULT_REFRESH_TOKEN_TTL_SECONDS: u64 = 2_592_000; // 30 days
+pub const DEFAULT_JWT_ALGORITHM: &str = "HS256";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "auth-service";
+pub const DEFAULT_SWEEP_INTERVAL_SECONDS: u64 = 300; // 5 minutes
+pub const DEFAULT_PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 900; // 15 minutes
+pub const DEFAULT_PASSWORD_RESET_URL: &str = "http://localhost:3000/reset-password";
+pub const DEFAULT_EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = 86_400; // 24 hours
+pub const DEFAULT_EMAIL_VERIFICATION_URL: &str = "http://localhost:3000/verify-email";
+pub const DEFAULT_EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS: u64 = 60;
+pub const DEFAULT_EMAIL_CHANGE_TOKEN_TTL_SECONDS: u64 = 3_600; // 1 hour
+pub const DEFAULT_EMAIL_CHANGE_URL: &str = "http://localhost:3000/confirm-email-change";
+pub const DEFAULT_SMTP_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_SMTP_POOL_SIZE: u32 = 4;
+pub const DEFAULT_EMAIL_API_TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+pub const DEFAULT_EMAIL_API_TIMEOUT_SECONDS: u64 = 10;
+
+// The `aud` of password reset tokens, which keeps them from passing for access tokens
+// or any other emailed token
+pub const PASSWORD_RESET_AUDIENCE: &str = "password-reset";
+// The `aud` of the tokens in email verification links
+pub const EMAIL_VERIFICATION_AUDIENCE: &str = "email-verification";
+// The `aud` of the tokens in the links confirming a change of address
+pub const EMAIL_CHANGE_AUDIENCE: &str = "email-change";
+
+// Access tokens are not scoped down: each one grants full use of its user's account
+pub const ACCESS_TOKEN_SCOPE: &str = "account";
+
+// Shown next to the account in authenticator apps
+pub const TOTP_ISSUER: &str = "Auth";
+// RFC 6238 defaults: a new code every 30 seconds, and one step of clock drift either way
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const TOTP_SKEW_STEPS: u64 = 1;
+
+// How many single-use recovery codes a user gets each time a set is issued
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Shown by the browser when creating a passkey
+pub const WEBAUTHN_RP_NAME: &str = "Auth";
+// How long a passkey registration or login challenge can be answered
+pub const PASSKEY_CEREMONY_TTL_SECONDS: u64 = 300; // 5 minutes
@@ -1,2 +1,6 @@
 pub mod constants;
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod client_info;
+pub mod signing_key;
+pub mod sweeper;
+pub mod webauthn;
@@ -0,0 +1,592 @@
+use std::fs;
+use std::str::FromStr;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use lazy_static::lazy_static;
+use openssl::pkey::{Id, PKey};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::auth::{longest_token_ttl_seconds, TOKEN_TTL_SECONDS};
+use super::constants::{
+    JWT_ALGORITHM, JWT_KEY_ID, JWT_PRIVATE_KEY_PATH, JWT_SECRET, JWT_SECRET_PATH,
+};
+
+lazy_static! {
+    // The keys access tokens are signed with and checked against
+    pub static ref KEYRING: RwLock<Keyring> = RwLock::new(Keyring::new(
+        SigningKey::from_config().expect("Failed to load the JWT signing key")
+    ));
+}
+
+// The keyring stays consistent even if a reload panicked, so a poisoned lock is still usable
+pub fn keyring() -> RwLockReadGuard<'static, Keyring> {
+    KEYRING.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Load the configured key again (after the key file was replaced) and make it the signing key.
+// Tokens signed by the key it replaces keep working until they expire.
+pub fn reload_keyring() -> Result<KeyringStatus, String> {
+    let key = SigningKey::from_config()?;
+    let mut keyring = KEYRING.write().unwrap_or_else(PoisonError::into_inner);
+    keyring.rotate(key)?;
+
+    Ok(keyring.status())
+}
+
+// Reload the keyring whenever the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_keyring_on_sighup() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        match reload_keyring() {
+            Ok(status) => println!("signing key reloaded, now signing with {}", status.kid),
+            Err(err) => eprintln!("failed to reload signing key: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+// One key signs new tokens. The keys it replaced still verify the tokens they signed
+// until those have expired, so rotating a key neither logs anyone out nor breaks the
+// links emailed to them. How long that is depends on the kind of token: a replaced key
+// stops verifying access tokens well before it stops verifying emailed links.
+pub struct Keyring {
+    current: SigningKey,
+    // Replaced keys, with the time (seconds since the epoch) they were replaced
+    retiring: Vec<(SigningKey, i64)>,
+}
+
+impl Keyring {
+    pub fn new(current: SigningKey) -> Self {
+        Self {
+            current,
+            retiring: Vec::new(),
+        }
+    }
+
+    // Make `key` the signing key. Reloading the current key changes nothing, but a new key
+    // under the current kid is refused: tokens name their key by kid, so it could not
+    // take over without breaking every token the current key signed.
+    pub fn rotate(&mut self, key: SigningKey) -> Result<(), String> {
+        if key.kid == self.current.kid {
+            if key.fingerprint != self.current.fingerprint {
+                return Err(format!(
+                    "The key changed but its kid is still {}; give the new key its own JWT_KEY_ID",
+                    key.kid
+                ));
+            }
+            return Ok(());
+        }
+
+        let now = Utc::now().timestamp();
+        let ttl_seconds = longest_token_ttl_seconds();
+        self.retiring.retain(|(retiring, replaced_at)| {
+            replaced_at + ttl_seconds > now && retiring.kid != key.kid
+        });
+
+        let previous = std::mem::replace(&mut self.current, key);
+        self.retiring.push((previous, now));
+        Ok(())
+    }
+
+    // Replace the retiring keys `seconds` earlier, as if that much time had passed
+    #[cfg(test)]
+    pub(crate) fn advance(&mut self, seconds: i64) {
+        for (_, replaced_at) in &mut self.retiring {
+            *replaced_at -= seconds;
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        self.current.sign(claims)
+    }
+
+    // The token's `kid` picks the key. Tokens without one, issued before key ids
+    // were introduced, can only have been signed by the current key.
+    // Tokens of this kind live `ttl_seconds`, so a key replaced longer ago than that
+    // cannot have signed one that is still valid and is not accepted for it.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+        ttl_seconds: i64,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key = match header.kid {
+            None => &self.current,
+            Some(kid) => self
+                .keys(ttl_seconds)
+                .find(|key| key.kid == kid)
+                .ok_or(ErrorKind::InvalidToken)?,
+        };
+
+        key.verify(token, validation)
+    }
+
+    // The public keys to publish at `/.well-known/jwks.json`. Other services only verify
+    // access tokens, so retiring keys are listed for as long as those can last.
+    pub fn jwk_set(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys(TOKEN_TTL_SECONDS)
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    pub fn status(&self) -> KeyringStatus {
+        KeyringStatus {
+            kid: self.current.kid.clone(),
+            retiring: self
+                .keys(longest_token_ttl_seconds())
+                .skip(1)
+                .map(|key| key.kid.clone())
+                .collect(),
+        }
+    }
+
+    // The current key first, then the retiring keys replaced less than `ttl_seconds` ago
+    fn keys(&self, ttl_seconds: i64) -> impl Iterator<Item = &SigningKey> {
+        let now = Utc::now().timestamp();
+        let retiring = self
+            .retiring
+            .iter()
+            .filter(move |(_, replaced_at)| replaced_at + ttl_seconds > now)
+            .map(|(key, _)| key);
+
+        std::iter::once(&self.current).chain(retiring)
+    }
+}
+
+// Which keys are in use, by kid
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyringStatus {
+    pub kid: String,
+    pub retiring: Vec<String>,
+}
+
+// A JWT signing key, named in each token's `kid` header.
+// Asymmetric keys publish their public half in the JWKS so other services can
+// verify tokens locally; an HMAC secret cannot be published and verifies only here.
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    // Tells keys apart whatever kid they were given: a hash of the secret or the RFC 7638 thumbprint
+    fingerprint: String,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl SigningKey {
+    // Without an explicit kid the key is named after a truncated hash of the secret,
+    // which reveals nothing a token signed with it does not already give away
+    pub fn hmac(secret: &[u8], kid: Option<String>) -> Self {
+        let fingerprint = URL_SAFE_NO_PAD.encode(Sha256::digest(secret));
+        let kid = kid.unwrap_or_else(|| format!("hs256-{}", &fingerprint[..HMAC_KEY_ID_LENGTH]));
+
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            fingerprint,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    // An RS256 key from an RSA private key, or an EdDSA key from an Ed25519 one (PKCS#8 PEM)
+    pub fn from_pem(algorithm: Algorithm, pem: &[u8], kid: Option<String>) -> Result<Self, String> {
+        let key = PKey::private_key_from_pem(pem).map_err(|err| err.to_string())?;
+
+        let (encoding_key, key_algorithm, parameters) = match algorithm {
+            Algorithm::RS256 => {
+                let rsa = key
+                    .rsa()
+                    .map_err(|_| "RS256 needs an RSA private key".to_string())?;
+                let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                    e: URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+                });
+                let encoding_key = EncodingKey::from_rsa_pem(pem).map_err(|err| err.to_string())?;
+                (encoding_key, KeyAlgorithm::RS256, parameters)
+            }
+            Algorithm::EdDSA => {
+                if key.id() != Id::ED25519 {
+                    return Err("EdDSA needs an Ed25519 private key".to_string());
+                }
+                let public_key = key.raw_public_key().map_err(|err| err.to_string())?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(public_key),
+                });
+                let encoding_key = EncodingKey::from_ed_pem(pem).map_err(|err| err.to_string())?;
+                (encoding_key, KeyAlgorithm::EdDSA, parameters)
+            }
+            other => return Err(format!("{:?} keys cannot be loaded from a PEM file", other)),
+        };
+
+        let fingerprint = thumbprint(&parameters)?;
+        let kid = kid.unwrap_or_else(|| fingerprint.clone());
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+        let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            fingerprint,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    // The key described by JWT_ALGORITHM, JWT_KEY_ID and JWT_PRIVATE_KEY_PATH (or, for HS256,
+    // JWT_SECRET_PATH or JWT_SECRET). Key files are read afresh on every call.
+    fn from_config() -> Result<Self, String> {
+        let algorithm = Algorithm::from_str(&JWT_ALGORITHM)
+            .map_err(|_| format!("Unknown JWT_ALGORITHM: {}", *JWT_ALGORITHM))?;
+
+        match algorithm {
+            Algorithm::HS256 => match JWT_SECRET_PATH.as_ref() {
+                Some(path) => {
+                    let secret =
+                        fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+                    let secret = secret.trim();
+                    if secret.is_empty() {
+                        return Err(format!("{}: the secret must not be empty", path));
+                    }
+                    Ok(Self::hmac(secret.as_bytes(), JWT_KEY_ID.clone()))
+                }
+                None => Ok(Self::hmac(JWT_SECRET.as_bytes(), JWT_KEY_ID.clone())),
+            },
+            Algorithm::RS256 | Algorithm::EdDSA => {
+                let path = JWT_PRIVATE_KEY_PATH.as_ref().ok_or(format!(
+                    "JWT_PRIVATE_KEY_PATH must be set for {:?}",
+                    algorithm
+                ))?;
+                let pem = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+                Self::from_pem(algorithm, &pem, JWT_KEY_ID.clone())
+            }
+            other => Err(format!("Unsupported JWT_ALGORITHM: {:?}", other)),
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let header = Header {
+            kid: Some(self.kid.clone()),
+            ..Header::new(self.algorithm)
+        };
+
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    // Checks the claims as `validation` asks, but only ever accepts this key's algorithm
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        let mut validation = validation.clone();
+        validation.algorithms = vec![self.algorithm];
+
+        decode::<T>(token, &self.decoding_key, &validation).map(|data| data.claims)
+    }
+}
+
+const HMAC_KEY_ID_LENGTH: usize = 8;
+
+// RFC 7638: the SHA-256 of the key's required members, in lexicographic order
+fn thumbprint(parameters: &AlgorithmParameters) -> Result<String, String> {
+    let members = match parameters {
+        AlgorithmParameters::RSA(rsa) => {
+            serde_json::json!({ "e": rsa.e, "kty": "RSA", "n": rsa.n })
+        }
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            serde_json::json!({ "crv": "Ed25519", "kty": "OKP", "x": okp.x })
+        }
+        _ => return Err("Unsupported key type".to_string()),
+    };
+
+    // Members are listed in lexicographic order above and serialised without whitespace
+    let members = serde_json::to_string(&members).map_err(|err| err.to_string())?;
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(members.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "test@example.com".to_owned(),
+            exp: (chrono::Utc::now().timestamp() + 600) as usize,
+        }
+    }
+
+    // The key swaps in its own algorithm, so the default validation suits every key
+    fn validation() -> Validation {
+        Validation::default()
+    }
+
+    fn rsa_pem() -> Vec<u8> {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        key.private_key_to_pem_pkcs8().unwrap()
+    }
+
+    fn ed25519_pem() -> Vec<u8> {
+        PKey::generate_ed25519()
+            .unwrap()
+            .private_key_to_pem_pkcs8()
+            .unwrap()
+    }
+
+    fn keyring_with(key: SigningKey) -> Keyring {
+        Keyring::new(key)
+    }
+
+    #[test]
+    fn test_hmac_round_trip() {
+        let key = SigningKey::hmac(b"secret", None);
+        let token = key.sign(&claims()).unwrap();
+
+        assert_eq!(decode_header(&token).unwrap().kid, Some(key.kid.clone()));
+        assert!(key.kid.starts_with("hs256-"));
+        assert_ne!(SigningKey::hmac(b"other secret", None).kid, key.kid);
+        assert_eq!(
+            key.verify::<TestClaims>(&token, &validation()).unwrap(),
+            claims()
+        );
+        assert!(keyring_with(key).jwk_set().keys.is_empty());
+    }
+
+    #[test]
+    fn test_rs256_round_trip_and_jwk() {
+        let key = SigningKey::from_pem(Algorithm::RS256, &rsa_pem(), None).unwrap();
+        let token = key.sign(&claims()).unwrap();
+
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid, Some(key.kid.clone()));
+        assert_eq!(
+            key.verify::<TestClaims>(&token, &validation()).unwrap(),
+            claims()
+        );
+
+        // The published key alone is enough to verify the token
+        let kid = key.kid.clone();
+        let jwks = keyring_with(key).jwk_set();
+        let jwk = jwks.find(&kid).expect("key is published");
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        assert!(
+            decode::<TestClaims>(&token, &decoding_key, &Validation::new(Algorithm::RS256)).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_eddsa_round_trip_and_jwk() {
+        let key = SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), Some("ed-1".to_owned()))
+            .unwrap();
+        let token = key.sign(&claims()).unwrap();
+
+        assert_eq!(key.kid, "ed-1");
+        assert_eq!(decode_header(&token).unwrap().alg, Algorithm::EdDSA);
+        assert_eq!(
+            key.verify::<TestClaims>(&token, &validation()).unwrap(),
+            claims()
+        );
+
+        let jwks = keyring_with(key).jwk_set();
+        let jwk = jwks.find("ed-1").expect("key is published");
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        assert!(
+            decode::<TestClaims>(&token, &decoding_key, &Validation::new(Algorithm::EdDSA)).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_from_pem_rejects_mismatched_key_type() {
+        assert!(SigningKey::from_pem(Algorithm::RS256, &ed25519_pem(), None).is_err());
+        assert!(SigningKey::from_pem(Algorithm::EdDSA, &rsa_pem(), None).is_err());
+        assert!(SigningKey::from_pem(Algorithm::HS256, &rsa_pem(), None).is_err());
+        assert!(SigningKey::from_pem(Algorithm::RS256, b"not a pem", None).is_err());
+    }
+
+    #[test]
+    fn test_kid_defaults_to_stable_thumbprint() {
+        let pem = ed25519_pem();
+        let key = SigningKey::from_pem(Algorithm::EdDSA, &pem, None).unwrap();
+
+        assert_eq!(key.kid.len(), 43);
+        assert_eq!(
+            SigningKey::from_pem(Algorithm::EdDSA, &pem, None)
+                .unwrap()
+                .kid,
+            key.kid
+        );
+        assert_ne!(
+            SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), None)
+                .unwrap()
+                .kid,
+            key.kid
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_other_keys() {
+        let key = SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), None).unwrap();
+        let other = SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), None).unwrap();
+        let hmac = SigningKey::hmac(b"secret", Some(key.kid.clone()));
+        let other_token = other.sign(&claims()).unwrap();
+        let hmac_token = hmac.sign(&claims()).unwrap();
+        let keyring = keyring_with(key);
+
+        // Signed by a key the keyring does not hold
+        assert!(keyring
+            .verify::<TestClaims>(&other_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_err());
+        // Same kid, but an HMAC signature must not pass for an asymmetric key
+        assert!(keyring
+            .verify::<TestClaims>(&hmac_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rotated_key_verifies_until_it_retires() {
+        let old = SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), None).unwrap();
+        let new = SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), None).unwrap();
+        let (old_kid, new_kid) = (old.kid.clone(), new.kid.clone());
+        let mut keyring = keyring_with(old);
+        let old_token = keyring.sign(&claims()).unwrap();
+
+        keyring.rotate(new).unwrap();
+        let new_token = keyring.sign(&claims()).unwrap();
+
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid,
+            Some(new_kid.clone())
+        );
+        assert!(keyring
+            .verify::<TestClaims>(&old_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_ok());
+        assert!(keyring
+            .verify::<TestClaims>(&new_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_ok());
+        assert_eq!(
+            keyring.status(),
+            KeyringStatus {
+                kid: new_kid.clone(),
+                retiring: vec![old_kid.clone()],
+            }
+        );
+        assert_eq!(keyring.jwk_set().keys.len(), 2);
+
+        // Once its access tokens have expired, the old key only verifies longer-lived tokens
+        // and is no longer published
+        keyring.advance(TOKEN_TTL_SECONDS);
+        assert!(keyring
+            .verify::<TestClaims>(&old_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_err());
+        assert!(keyring
+            .verify::<TestClaims>(&old_token, &validation(), longest_token_ttl_seconds())
+            .is_ok());
+        assert!(keyring.jwk_set().find(&old_kid).is_none());
+        assert_eq!(keyring.status().retiring, vec![old_kid.clone()]);
+
+        // Once every token it signed has expired, the old key is gone
+        keyring.advance(longest_token_ttl_seconds() - TOKEN_TTL_SECONDS);
+        assert!(keyring
+            .verify::<TestClaims>(&old_token, &validation(), longest_token_ttl_seconds())
+            .is_err());
+        assert!(keyring
+            .verify::<TestClaims>(&new_token, &validation(), TOKEN_TTL_SECONDS)
+            .is_ok());
+        assert_eq!(keyring.status().retiring, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_rotate_to_current_kid_changes_nothing() {
+        let mut keyring = keyring_with(SigningKey::hmac(b"secret", None));
+
+        keyring.rotate(SigningKey::hmac(b"secret", None)).unwrap();
+
+        assert!(keyring.status().retiring.is_empty());
+    }
+
+    #[test]
+    fn test_rotate_refuses_new_key_under_current_kid() {
+        let kid = Some("key-1".to_owned());
+        let mut keyring = keyring_with(SigningKey::hmac(b"secret", kid.clone()));
+        let token = keyring.sign(&claims()).unwrap();
+
+        assert!(keyring
+            .rotate(SigningKey::hmac(b"new secret", kid.clone()))
+            .is_err());
+        assert!(keyring
+            .rotate(SigningKey::from_pem(Algorithm::EdDSA, &ed25519_pem(), kid).unwrap())
+            .is_err());
+
+        // The current key is kept and still verifies what it signed
+        assert!(keyring
+            .verify::<TestClaims>(&token, &validation(), TOKEN_TTL_SECONDS)
+            .is_ok());
+        assert!(keyring.status().retiring.is_empty());
+    }
+
+    #[test]
+    fn test_token_without_kid_is_checked_against_current_key() {
+        let key = SigningKey::hmac(b"secret", None);
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let mut keyring = keyring_with(key);
+
+        assert!(keyring
+            .verify::<TestClaims>(&token, &validation(), TOKEN_TTL_SECONDS)
+            .is_ok());
+
+        keyring
+            .rotate(SigningKey::hmac(b"new secret", None))
+            .unwrap();
+        assert!(keyring
+            .verify::<TestClaims>(&token, &validation(), TOKEN_TTL_SECONDS)
+            .is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use crate::AppState;
+
+// Running totals of what the sweeper has purged since startup
+#[derive(Debug, Default)]
+pub struct SweeperMetrics {
+    runs: AtomicU64,
+    failures: AtomicU64,
+    // Seconds since the epoch, 0 until the first sweep has finished
+    last_run_at: AtomicI64,
+    banned_tokens: AtomicU64,
+    two_fa_codes: AtomicU64,
+    passkey_ceremonies: AtomicU64,
+    refresh_tokens: AtomicU64,
+    sessions: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweeperMetricsSnapshot {
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run_at: Option<i64>,
+    pub banned_tokens: u64,
+    pub two_fa_codes: u64,
+    pub passkey_ceremonies: u64,
+    pub refresh_tokens: u64,
+    pub sessions: u64,
+}
+
+impl SweeperMetrics {
+    pub fn snapshot(&self) -> SweeperMetricsSnapshot {
+        let last_run_at = self.last_run_at.load(Ordering::Relaxed);
+
+        SweeperMetricsSnapshot {
+            runs: self.runs.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            last_run_at: (last_run_at > 0).then_some(last_run_at),
+            banned_tokens: self.banned_tokens.load(Ordering::Relaxed),
+            two_fa_codes: self.two_fa_codes.load(Ordering::Relaxed),
+            passkey_ceremonies: self.passkey_ceremonies.load(Ordering::Relaxed),
+            refresh_tokens: self.refresh_tokens.load(Ordering::Relaxed),
+            sessions: self.sessions.load(Ordering::Relaxed),
+        }
+    }
+
+    // Adds a store's purge to its total, or counts the failure
+    fn record<E: std::fmt::Debug>(&self, store: &str, counter: &AtomicU64, purged: Result<u64, E>) {
+        match purged {
+            Ok(purged) => {
+                counter.fetch_add(purged, Ordering::Relaxed);
+            }
+            Err(err) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Failed to purge expired {}: {:?}", store, err);
+            }
+        }
+    }
+}
+
+// Periodically purges expired entries from every store holding any, so the
+// in-memory ones don't grow forever. Backends that expire entries by
+// themselves, like Redis, have nothing to do.
+pub struct Sweeper {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Sweeper {
+    // The first sweep runs straight away, then one every `interval`
+    pub fn spawn(app_state: AppState, interval: Duration) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // A sweep that overran just delays the next one rather than causing a burst
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = ticker.tick() => sweep(&app_state).await,
+                }
+            }
+        });
+
+        Self { stop, handle }
+    }
+
+    // Lets a sweep in progress finish, then ends the task
+    pub async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.await;
+    }
+}
+
+// Purge every store once, recording the outcome in the app's sweeper metrics
+pub async fn sweep(app_state: &AppState) {
+    let metrics = &app_state.sweeper_metrics;
+
+    // Each store is locked only for its own purge
+    let purged = app_state
+        .banned_token_store
+        .write()
+        .await
+        .purge_expired()
+        .await;
+    metrics.record("banned tokens", &metrics.banned_tokens, purged);
+
+    let purged = app_state
+        .two_fa_code_store
+        .write()
+        .await
+        .purge_expired()
+        .await;
+    metrics.record("2FA codes", &metrics.two_fa_codes, purged);
+
+    let purged = app_state
+        .credential_store
+        .write()
+        .await
+        .purge_expired()
+        .await;
+    metrics.record("passkey ceremonies", &metrics.passkey_ceremonies, purged);
+
+    let purged = app_state
+        .refresh_token_store
+        .write()
+        .await
+        .purge_expired()
+        .await;
+    metrics.record("refresh tokens", &metrics.refresh_tokens, purged);
+
+    let purged = app_state
+        .session_store
+        .write()
+        .await
+        .purge_expired()
+        .await;
+    metrics.record("sessions", &metrics.sessions, purged);
+
+    metrics.runs.fetch_add(1, Ordering::Relaxed);
+    metrics
+        .last_run_at
+        .store(Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore};
+    use crate::domain::Email;
+    use crate::{
+        HashMap2FaTokenStore, HashMapCredentialStore, HashMapRefreshTokenStore,
+        HashMapSessionStore, HashMapUserStore, HashSetBannedTokenStore, MockEmailClient,
+    };
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn app_state(
+        banned_token_store: HashSetBannedTokenStore,
+        two_fa_code_store: HashMap2FaTokenStore,
+    ) -> AppState {
+        AppState::new(
+            Arc::new(RwLock::new(HashMapUserStore::new())),
+            Arc::new(RwLock::new(banned_token_store)),
+            Arc::new(RwLock::new(two_fa_code_store)),
+            Arc::new(RwLock::new(HashMapCredentialStore::new())),
+            Arc::new(RwLock::new(HashMapRefreshTokenStore::new())),
+            Arc::new(RwLock::new(HashMapSessionStore::new())),
+            Arc::new(MockEmailClient {}),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sweep_purges_and_counts_expired_entries() {
+        let now = Utc::now().timestamp() as usize;
+        let mut banned_token_store = HashSetBannedTokenStore::default();
+        banned_token_store
+            .store
+            .insert("expired".to_owned(), now - 1);
+        banned_token_store
+            .store
+            .insert("live".to_owned(), now + 600);
+        let mut two_fa_code_store = HashMap2FaTokenStore::new();
+        two_fa_code_store
+            .add_code(
+                Email::parse("user@example.com").unwrap(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        let app_state = app_state(banned_token_store, two_fa_code_store);
+
+        sweep(&app_state).await;
+        sweep(&app_state).await;
+
+        let snapshot = app_state.sweeper_metrics.snapshot();
+        assert_eq!(snapshot.runs, 2);
+        assert_eq!(snapshot.failures, 0);
+        assert!(snapshot.last_run_at.is_some());
+        assert_eq!(snapshot.banned_tokens, 1);
+        assert_eq!(snapshot.two_fa_codes, 0);
+        assert_eq!(snapshot.refresh_tokens, 0);
+        assert_eq!(snapshot.sessions, 0);
+        assert_eq!(
+            app_state
+                .banned_token_store
+                .read()
+                .await
+                .contains("live")
+                .await,
+            Ok(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawned_sweeper_runs_until_stopped() {
+        let app_state = app_state(
+            HashSetBannedTokenStore::default(),
+            HashMap2FaTokenStore::new(),
+        );
+        assert_eq!(app_state.sweeper_metrics.snapshot().last_run_at, None);
+
+        let sweeper = Sweeper::spawn(app_state.clone(), Duration::from_secs(3600));
+        while app_state.sweeper_metrics.snapshot().runs == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), sweeper.stop())
+            .await
+            .expect("Sweeper did not stop");
+        assert_eq!(app_state.sweeper_metrics.snapshot().runs, 1);
+    }
+}
@@ -0,0 +1,29 @@
+use lazy_static::lazy_static;
+use webauthn_rs::prelude::{Url, Uuid};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+use crate::domain::user::UserId;
+
+use super::constants::{WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME, WEBAUTHN_RP_ORIGIN};
+
+lazy_static! {
+    // The relying party every passkey registration and login is checked against
+    pub static ref WEBAUTHN: Webauthn = build_webauthn();
+}
+
+fn build_webauthn() -> Webauthn {
+    let origin = Url::parse(&WEBAUTHN_RP_ORIGIN).expect("WEBAUTHN_RP_ORIGIN must be a valid URL.");
+
+    WebauthnBuilder::new(&WEBAUTHN_RP_ID, &origin)
+        .expect("WEBAUTHN_RP_ID must be a valid domain for WEBAUTHN_RP_ORIGIN.")
+        .rp_name(WEBAUTHN_RP_NAME)
+        .build()
+        .expect("Failed to configure WebAuthn")
+}
+
+// The WebAuthn user handle for an account. It is the user's id, which unlike their email
+// never changes, so every passkey the user registers, before or after moving to another
+// address, is recognised by the authenticator as belonging to the same account.
+pub fn user_unique_id(id: &UserId) -> Uuid {
+    Uuid::parse_str(id.as_ref()).expect("User ids are UUIDs")
+}
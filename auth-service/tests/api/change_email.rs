@@ -0,0 +1,267 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::Email;
+use auth_service::utils::auth::generate_password_reset_token;
+use auth_service::{SessionsResponse, TwoFactorAuthResponse};
+
+const CONFIRMATION_SUBJECT: &str = "Confirm your new email address";
+const NOTICE_SUBJECT: &str = "Your email address is about to change";
+
+async fn change_email(app: &TestApp, new_email: &str, password: &str) -> u16 {
+    app.post_change_email(&serde_json::json!({
+        "newEmail": new_email,
+        "password": password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+// Ask to move to `new_email` and take the token out of the confirmation sent there
+async fn request_change_token(app: &TestApp, new_email: &str) -> String {
+    assert_eq!(change_email(app, new_email, "password123").await, 200);
+
+    let sent = app
+        .email_client
+        .wait_for_email(new_email, CONFIRMATION_SUBJECT)
+        .await;
+    sent.content
+        .split("?token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("No confirmation link in email")
+        .to_owned()
+}
+
+async fn confirm(app: &TestApp, token: &str) -> u16 {
+    app.post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+async fn should_change_email(app: TestApp) {
+    let email = app.signup(false).await;
+    let (old_token, _) = app.login_for_tokens(&email).await;
+    let new_email = get_random_email();
+
+    let token = request_change_token(&app, &new_email).await;
+
+    // The old address is told, and nothing changes until the new one confirms
+    let notice = app
+        .email_client
+        .wait_for_email(&email, NOTICE_SUBJECT)
+        .await;
+    assert!(notice.content.contains(&new_email));
+    assert_eq!(app.verify_token(&old_token).await, 200);
+
+    assert_eq!(confirm(&app, &token).await, 200);
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(&new_email).unwrap())
+        .await
+        .unwrap();
+    assert!(user.email_verified);
+
+    // Access tokens name the user by id, so they carry on working, as does the session
+    assert_eq!(app.verify_token(&old_token).await, 200);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+    let sessions = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .unwrap()
+        .sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        401
+    );
+    assert_eq!(
+        app.login(&new_email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_change_email() {
+    should_change_email(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_change_email_with_sqlite() {
+    should_change_email(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_move_pending_2fa_code() {
+    let app = TestApp::new().await;
+    let email = app.signup(true).await;
+    let new_email = get_random_email();
+
+    // Log in through 2FA, to be able to ask for the change
+    let response = app.login(&email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+    let attempt = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    let entry = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&Email::parse(&email).unwrap())
+        .await
+        .unwrap();
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": attempt.login_attempt_id,
+            "2FACode": entry.code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = request_change_token(&app, &new_email).await;
+
+    // Another login is waiting for its code when the change goes through
+    let response = app.login(&email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+    let attempt = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    let entry = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&Email::parse(&email).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(confirm(&app, &token).await, 200);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": new_email,
+            "loginAttemptId": attempt.login_attempt_id,
+            "2FACode": entry.code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_only_accept_token_once() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+    let token = request_change_token(&app, &get_random_email()).await;
+
+    assert_eq!(confirm(&app, &token).await, 200);
+    assert_eq!(confirm(&app, &token).await, 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+
+    // A token for another purpose does not pass for an email change token
+    let reset_token = generate_password_reset_token(&Email::parse(&email).unwrap()).unwrap();
+    for token in ["invalid", reset_token.as_str()] {
+        assert_eq!(confirm(&app, token).await, 401);
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_and_send_nothing_if_new_email_is_taken() {
+    let app = TestApp::new().await;
+    let taken = app.signup(false).await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    // The same answer as for a free address, so it does not give the account away
+    assert_eq!(change_email(&app, &taken, "password123").await, 200);
+    let subjects = |recipient: &str| {
+        app.email_client
+            .sent_to(recipient)
+            .into_iter()
+            .map(|email| email.subject)
+            .collect::<Vec<_>>()
+    };
+    assert!(!subjects(&taken).contains(&CONFIRMATION_SUBJECT.to_owned()));
+    assert!(!subjects(&email).contains(&NOTICE_SUBJECT.to_owned()));
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_is_taken_before_confirmation() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+    let new_email = get_random_email();
+    let token = request_change_token(&app, &new_email).await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": new_email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    assert_eq!(confirm(&app, &token).await, 409);
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_is_wrong() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+    let new_email = get_random_email();
+
+    for password in ["wrong-password", "short"] {
+        assert_eq!(change_email(&app, &new_email, password).await, 401);
+    }
+    assert!(app.email_client.sent_to(&new_email).is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_email_is_invalid() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    for new_email in ["not-an-email", email.as_str()] {
+        assert_eq!(change_email(&app, new_email, "password123").await, 400);
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+
+    assert_eq!(
+        change_email(&app, &get_random_email(), "password123").await,
+        400
+    );
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app.post_confirm_email_change(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
@@ -0,0 +1,142 @@
+use crate::helpers::TestApp;
+
+async fn change_password(app: &TestApp, body: serde_json::Value) -> u16 {
+    app.post_change_password(&body).await.status().as_u16()
+}
+
+async fn should_change_password(app: TestApp) {
+    let email = app.signup(false).await;
+    let (other_token, _) = app.login_for_tokens(&email).await;
+    let (token, _) = app.login_for_tokens(&email).await;
+
+    let status = change_password(
+        &app,
+        serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password",
+        }),
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        401
+    );
+    assert_eq!(
+        app.login(&email, "new-password").await.status().as_u16(),
+        200
+    );
+
+    // Other sessions are left alone unless asked for
+    assert_eq!(app.verify_token(&token).await, 200);
+    assert_eq!(app.verify_token(&other_token).await, 200);
+}
+
+#[tokio::test]
+async fn should_return_200_and_change_password() {
+    should_change_password(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_change_password_with_sqlite() {
+    should_change_password(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_log_out_other_sessions_if_asked() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let (other_token, _) = app.login_for_tokens(&email).await;
+    let (token, _) = app.login_for_tokens(&email).await;
+
+    let status = change_password(
+        &app,
+        serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password",
+            "logoutOtherSessions": true,
+        }),
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    assert_eq!(app.verify_token(&other_token).await, 401);
+    assert_eq!(app.verify_token(&token).await, 200);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+
+    let response = app.get_sessions().await;
+    let sessions: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_wrong() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    for current_password in ["wrong-password", "short"] {
+        let status = change_password(
+            &app,
+            serde_json::json!({
+                "currentPassword": current_password,
+                "newPassword": "new-password",
+            }),
+        )
+        .await;
+        assert_eq!(status, 401);
+    }
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_too_weak() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    let status = change_password(
+        &app,
+        serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "short",
+        }),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let status = change_password(
+        &app,
+        serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password",
+        }),
+    )
+    .await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    let status = change_password(&app, serde_json::json!({ "newPassword": "new-password" })).await;
+    assert_eq!(status, 422);
+}
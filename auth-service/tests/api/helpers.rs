@@ -1,32 +1,128 @@
-use auth_service::utils::constants::test;
-use auth_service::{AppState, Application, BannedStoreType, HashMap2FaTokenStore, HashMapUserStore, HashSetBannedTokenStore, MockEmailClient, TwoFACodeStoreType};
+use auth_service::domain::{Email, EmailClient};
+use auth_service::utils::constants::{
+    env, test, DATABASE_URL, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME,
+};
+use auth_service::{
+    get_postgres_pool, AppState, Application, BannedStoreType, HashMap2FaTokenStore,
+    HashMapCredentialStore, HashMapRefreshTokenStore, HashMapSessionStore, HashSetBannedTokenStore,
+    PostgresUserStore, RefreshTokenStoreType, SessionStoreType, SqliteStore,
+    TwoFACodeStoreType, UserStoreType,
+};
 use reqwest::cookie::Jar;
-use std::sync::Arc;
+use reqwest::Url;
+use sqlx::postgres::{PgConnectOptions, PgConnection};
+use sqlx::{Connection, Executor, PgPool};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use tempfile::TempDir;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+// The trusted service the tests authenticate as at `/introspect` and `/revoke`
+pub const TEST_CLIENT_ID: &str = "test-client";
+pub const TEST_CLIENT_SECRET: &str = "test-secret";
+
+static CLIENT_CREDENTIALS: Once = Once::new();
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub email_client: Arc<TestEmailClient>,
     pub http_client: reqwest::Client,
+    database: TestDatabase,
+}
+
+// The per-test database, removed again when the `TestApp` is dropped
+enum TestDatabase {
+    Postgres(String),
+    Sqlite { _dir: TempDir },
 }
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::new_with_postgres(false).await
+    }
+
+    // Logins are refused until the user has verified their address
+    pub async fn new_requiring_verified_email() -> Self {
+        Self::new_with_postgres(true).await
+    }
+
+    async fn new_with_postgres(require_verified_email: bool) -> Self {
+        let (pg_pool, db_name) = configure_postgresql().await;
+
         let banned_token_store = Arc::new(RwLock::new(HashSetBannedTokenStore::default()));
-        let user_store = Arc::new(RwLock::new(HashMapUserStore::new()));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
         let two_fa_code_store = Arc::new(RwLock::new(HashMap2FaTokenStore::new()));
-        let mock_email_client = Arc::new(MockEmailClient);
-
+        let refresh_token_store = Arc::new(RwLock::new(HashMapRefreshTokenStore::new()));
+        let session_store = Arc::new(RwLock::new(HashMapSessionStore::new()));
 
-        let app_state = AppState::new(
+        Self::start(
             user_store,
+            banned_token_store,
+            two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            require_verified_email,
+            TestDatabase::Postgres(db_name),
+        )
+        .await
+    }
+
+    // Runs every store off a single SQLite file in a temporary directory
+    pub async fn new_with_sqlite() -> Self {
+        let dir = TempDir::new().expect("Failed to create temp dir");
+        let sqlite_store = SqliteStore::open(dir.path().join("auth.db"))
+            .await
+            .expect("Failed to open SQLite store");
+
+        Self::start(
+            Arc::new(RwLock::new(sqlite_store.clone())),
+            Arc::new(RwLock::new(sqlite_store.clone())),
+            Arc::new(RwLock::new(sqlite_store.clone())),
+            Arc::new(RwLock::new(sqlite_store.clone())),
+            Arc::new(RwLock::new(sqlite_store)),
+            false,
+            TestDatabase::Sqlite { _dir: dir },
+        )
+        .await
+    }
+
+    async fn start(
+        user_store: UserStoreType,
+        banned_token_store: BannedStoreType,
+        two_fa_code_store: TwoFACodeStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
+        require_verified_email: bool,
+        database: TestDatabase,
+    ) -> Self {
+        // Set before any request can make the service read it
+        CLIENT_CREDENTIALS.call_once(|| {
+            std::env::set_var(
+                env::CLIENT_CREDENTIALS_ENV_VAR,
+                format!("{}:{}", TEST_CLIENT_ID, TEST_CLIENT_SECRET),
+            )
+        });
+
+        let email_client = Arc::new(TestEmailClient::default());
+
+        let mut app_state = AppState::new(
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
-            mock_email_client
+            Arc::new(RwLock::new(HashMapCredentialStore::new())),
+            refresh_token_store.clone(),
+            session_store.clone(),
+            email_client.clone(),
         );
+        app_state.require_verified_email = require_verified_email;
 
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
@@ -51,14 +147,19 @@ impl TestApp {
             cookie_jar,
             http_client,
             address,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            email_client,
+            database,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -91,6 +192,38 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/logout-all"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(self.url("/sessions"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(self.url(&format!("/sessions/{}", id)))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/refresh"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
         self.http_client
             .post(self.url("/verify-2fa"))
@@ -112,11 +245,410 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(self.url("/.well-known/jwks.json"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_signing_key(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.post(self.url("/admin/rotate-signing-key"));
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    // Introspects `token` as the given client, if any
+    pub async fn post_introspect(
+        &self,
+        token: &str,
+        client: Option<(&str, &str)>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(self.url("/introspect"))
+            .form(&[("token", token)]);
+        if let Some((client_id, secret)) = client {
+            request = request.basic_auth(client_id, Some(secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    // Revokes `token` as the given client, if any
+    pub async fn post_revoke(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+        client: Option<(&str, &str)>,
+    ) -> reqwest::Response {
+        let mut form = vec![("token", token)];
+        if let Some(token_type_hint) = token_type_hint {
+            form.push(("token_type_hint", token_type_hint));
+        }
+
+        let mut request = self.http_client.post(self.url("/revoke")).form(&form);
+        if let Some((client_id, secret)) = client {
+            request = request.basic_auth(client_id, Some(secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_as_admin(&self, token: &str, admin_token: &str) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/revoke"))
+            .form(&[("token", token)])
+            .bearer_auth(admin_token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sweeper_metrics(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(self.url("/admin/sweeper-metrics"));
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/enroll-totp"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_totp<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/confirm-totp"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/recovery-codes"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/passkey/register/start"))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_finish<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/passkey/register/finish"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/passkey/login/start"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/passkey/login/finish"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_forgot_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/password/forgot"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/password/reset"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/password/change"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/verify-email"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/verify-email/resend"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/email/change"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(self.url("/email/change/confirm"))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Sign up a user with a random address and the password `password123`, returning the address
+    pub async fn signup(&self, requires_2fa: bool) -> String {
+        let email = get_random_email();
+
+        let response = self
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+                "requires2FA": requires_2fa
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        email
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> reqwest::Response {
+        self.post_login(&serde_json::json!({
+            "email": email,
+            "password": password,
+        }))
+        .await
+    }
+
+    // Log in with `password123`, returning the access and refresh tokens of the new
+    // session. The app's cookies become this session's.
+    pub async fn login_for_tokens(&self, email: &str) -> (String, String) {
+        let response = self.login(email, "password123").await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let cookie = |name| get_cookie(&response, name).expect("No cookie found");
+        (cookie(JWT_COOKIE_NAME), cookie(REFRESH_COOKIE_NAME))
+    }
+
+    pub async fn verify_token(&self, token: &str) -> u16 {
+        self.post_verify_token(&serde_json::json!({ "token": token }))
+            .await
+            .status()
+            .as_u16()
+    }
+
+    // Put a cookie in the jar, as if the service had set it
+    pub fn set_cookie(&self, name: &str, value: &str) {
+        self.cookie_jar.add_cookie_str(
+            &format!("{}={}; HttpOnly; SameSite=Strict; Path=/", name, value),
+            &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+        );
+    }
+
     fn url(&self, path: &str) -> String {
         self.address.to_string() + path
     }
 }
 
+// Keeps every email the service sends, so tests can follow the links in them
+#[derive(Default)]
+pub struct TestEmailClient {
+    sent: Mutex<Vec<SentEmail>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+impl TestEmailClient {
+    pub fn sent_to(&self, recipient: &str) -> Vec<SentEmail> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|email| email.recipient == recipient)
+            .cloned()
+            .collect()
+    }
+
+    // The latest email to `recipient` with `subject`. Some emails are sent in the
+    // background, after the response, so they are waited for.
+    pub async fn wait_for_email(&self, recipient: &str, subject: &str) -> SentEmail {
+        for _ in 0..50 {
+            let sent = self.sent_to(recipient);
+            if let Some(email) = sent.into_iter().rev().find(|email| email.subject == subject) {
+                return email;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("No email sent to {} with subject {}", recipient, subject);
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for TestEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        self.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+
+        Ok(())
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        // A SQLite temp dir cleans itself up when dropped
+        let TestDatabase::Postgres(db_name) = &self.database else {
+            return;
+        };
+
+        // Drop is synchronous and may run inside the test's runtime,
+        // so the database is removed from a separate thread with its own runtime.
+        let db_name = db_name.clone();
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build cleanup runtime")
+                .block_on(delete_database(&db_name));
+        })
+        .join()
+        .expect("Failed to clean up test database");
+    }
+}
+
+// Every test gets a fresh, fully migrated database
+async fn configure_postgresql() -> (PgPool, String) {
+    let postgresql_conn_url = DATABASE_URL.to_owned();
+    let db_name = Uuid::new_v4().to_string();
+
+    let mut connection = PgConnection::connect(&postgresql_conn_url)
+        .await
+        .expect("Failed to connect to Postgres");
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, db_name).as_str())
+        .await
+        .expect("Failed to create database.");
+
+    let postgresql_conn_url_with_db = format!("{}/{}", postgresql_conn_url, db_name);
+    let pg_pool = get_postgres_pool(&postgresql_conn_url_with_db)
+        .await
+        .expect("Failed to create Postgres connection pool!");
+
+    sqlx::migrate!()
+        .run(&pg_pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    (pg_pool, db_name)
+}
+
+async fn delete_database(db_name: &str) {
+    let connection_options = PgConnectOptions::from_str(&DATABASE_URL)
+        .expect("Failed to parse PostgreSQL connection string");
+
+    let mut connection = PgConnection::connect_with(&connection_options)
+        .await
+        .expect("Failed to connect to Postgres");
+
+    // FORCE terminates the connections still held by the app's pool
+    connection
+        .execute(format!(r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE);"#, db_name).as_str())
+        .await
+        .expect("Failed to drop the database.");
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
+
+// The value of the cookie `name` set by `response`
+pub fn get_cookie(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_owned())
+}
@@ -0,0 +1,163 @@
+use crate::helpers::{get_random_email, TestApp, TEST_CLIENT_ID, TEST_CLIENT_SECRET};
+use auth_service::domain::data_stores::{Session, SessionId};
+use auth_service::domain::user::UserId;
+use auth_service::domain::Email;
+use auth_service::utils::auth::generate_auth_cookie;
+use auth_service::utils::constants::{ACCESS_TOKEN_SCOPE, JWT_COOKIE_NAME};
+use auth_service::IntrospectResponse;
+
+const CLIENT: Option<(&str, &str)> = Some((TEST_CLIENT_ID, TEST_CLIENT_SECRET));
+
+// Sign up and log in, returning the user's email and access token
+async fn login(app: &TestApp) -> (String, String) {
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    (email, token)
+}
+
+async fn introspect(app: &TestApp, token: &str) -> (IntrospectResponse, serde_json::Value) {
+    let response = app.post_introspect(token, CLIENT).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .expect("Could not deserialize response body");
+    let introspection = serde_json::from_value(body.clone())
+        .expect("Could not deserialize response body to IntrospectResponse");
+
+    (introspection, body)
+}
+
+#[tokio::test]
+async fn should_describe_active_token() {
+    let app = TestApp::new().await;
+    let (email, token) = login(&app).await;
+
+    let (introspection, _) = introspect(&app, &token).await;
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(&email).unwrap())
+        .await
+        .unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(user.id.as_ref()));
+    assert_eq!(introspection.username, Some(email));
+    assert_eq!(introspection.scope.as_deref(), Some(ACCESS_TOKEN_SCOPE));
+    assert_eq!(introspection.token_type.as_deref(), Some("Bearer"));
+    assert!(introspection.exp > introspection.iat);
+    assert!(introspection.jti.is_some());
+    assert_eq!(introspection.two_fa_enabled, Some(false));
+    assert_eq!(introspection.two_fa_method, None);
+}
+
+#[tokio::test]
+async fn should_report_2fa_status() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // A token for a session of its own, as the 2FA login would give
+    let email = Email::parse(&email).expect("Invalid email");
+    let user = app.user_store.read().await.get_user(&email).await.unwrap();
+    let session = Session::new(SessionId::default(), email, None, None);
+    let cookie =
+        generate_auth_cookie(&user.id, &session.id).expect("Failed to generate auth cookie");
+    app.session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .expect("Failed to add session");
+
+    let (introspection, _) = introspect(&app, cookie.value()).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.two_fa_enabled, Some(true));
+    assert_eq!(introspection.two_fa_method.as_deref(), Some("email"));
+}
+
+#[tokio::test]
+async fn should_report_banned_or_invalid_token_as_inactive() {
+    let app = TestApp::new().await;
+    let (_, token) = login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in [token.as_str(), "invalid"] {
+        let (introspection, body) = introspect(&app, token).await;
+
+        assert!(!introspection.active);
+        // Nothing but `active` is given away about an inactive token
+        assert_eq!(body, serde_json::json!({ "active": false }));
+    }
+}
+
+#[tokio::test]
+async fn should_report_token_of_unknown_user_as_inactive() {
+    let app = TestApp::new().await;
+
+    let cookie = generate_auth_cookie(&UserId::default(), &SessionId::default())
+        .expect("Failed to generate auth cookie");
+
+    let (introspection, _) = introspect(&app, cookie.value()).await;
+    assert!(!introspection.active);
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_client_credentials() {
+    let app = TestApp::new().await;
+    let (_, token) = login(&app).await;
+
+    for client in [
+        None,
+        Some((TEST_CLIENT_ID, "wrong-secret")),
+        Some(("other-client", TEST_CLIENT_SECRET)),
+    ] {
+        let response = app.post_introspect(&token, client).await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response
+                .headers()
+                .get("www-authenticate")
+                .and_then(|value| value.to_str().ok()),
+            Some("Basic")
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::helpers::TestApp;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::signing_key::keyring;
+
+#[tokio::test]
+async fn should_return_200_with_key_set() {
+    let app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body to JSON");
+    // The tests sign with JWT_SECRET, which is never published
+    assert_eq!(jwks, serde_json::json!({ "keys": [] }));
+}
+
+#[tokio::test]
+async fn should_name_signing_key_in_token_header() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "user@example.com",
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": "user@example.com",
+            "password": "password123",
+        }))
+        .await;
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    let header = jsonwebtoken::decode_header(auth_cookie.value()).expect("Invalid token header");
+    assert_eq!(header.kid, Some(keyring().status().kid));
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_token_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_rotate_signing_key(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_for_wrong_admin_token() {
+    let app = TestApp::new().await;
+
+    // No ADMIN_TOKEN is configured for the tests, so no token is accepted
+    let response = app.post_rotate_signing_key(Some("admin-token")).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
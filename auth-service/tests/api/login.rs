@@ -144,5 +144,5 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         .await
         .expect("get login attempt & code");
 
-    assert_eq!(result.0.as_ref(), &content.login_attempt_id);
+    assert_eq!(result.login_attempt_id.as_ref(), &content.login_attempt_id);
 }
@@ -1,4 +1,5 @@
 use crate::helpers::TestApp;
+use auth_service::utils::auth::validate_token;
 use auth_service::{utils::constants::JWT_COOKIE_NAME};
 use reqwest::Url;
 
@@ -46,8 +47,22 @@ async fn should_return_200_if_valid_jwt_cookie() {
     })).await;
     assert_eq!(response.status().as_u16(), 200);
 
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    let (claims, _) = validate_token(token.value(), &app.user_store)
+        .await
+        .expect("Could not validate auth token");
+
     let response = app.post_logout().await;
     assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        app.banned_token_store.read().await.contains(&claims.jti).await,
+        Ok(true)
+    );
 }
 
 #[tokio::test]
@@ -0,0 +1,99 @@
+use crate::helpers::TestApp;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use reqwest::Url;
+
+// Every session of the user ends, not just the one asking
+async fn should_revoke_every_session(app: TestApp) {
+    let email = app.signup(false).await;
+    let (first_token, first_refresh_token) = app.login_for_tokens(&email).await;
+    let (second_token, _) = app.login_for_tokens(&email).await;
+    assert_eq!(app.verify_token(&first_token).await, 200);
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(app.verify_token(&first_token).await, 401);
+    assert_eq!(app.verify_token(&second_token).await, 401);
+
+    app.set_cookie(REFRESH_COOKIE_NAME, &first_refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_every_session() {
+    should_revoke_every_session(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_every_session_with_sqlite() {
+    should_revoke_every_session(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_remove_cookies() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Logging out everywhere includes here
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_leave_other_users_logged_in() {
+    let app = TestApp::new().await;
+    let other_email = app.signup(false).await;
+    let (other_token, _) = app.login_for_tokens(&other_email).await;
+
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(app.verify_token(&other_token).await, 200);
+}
+
+#[tokio::test]
+async fn should_accept_logins_after_logout_all() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    app.login_for_tokens(&email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (token, _) = app.login_for_tokens(&email).await;
+    assert_eq!(app.verify_token(&token).await, 200);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -4,4 +4,19 @@ mod signup;
 mod root;
 mod verify_token;
 mod verify_2fa;
-mod logout;
\ No newline at end of file
+mod logout;
+mod logout_all;
+mod sqlite;
+mod totp;
+mod recovery_codes;
+mod passkey;
+mod refresh;
+mod revoke;
+mod jwks;
+mod introspect;
+mod sweeper;
+mod sessions;
+mod password_reset;
+mod change_password;
+mod verify_email;
+mod change_email;
@@ -0,0 +1,236 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, WEBAUTHN_RP_ORIGIN};
+use auth_service::{PasskeyLoginChallenge, SignUpResponse, TwoFactorAuthResponse};
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+use webauthn_authenticator_rs::WebauthnAuthenticator;
+use webauthn_rs::prelude::{CreationChallengeResponse, Url};
+
+type Authenticator = WebauthnAuthenticator<SoftPasskey>;
+
+fn origin() -> Url {
+    Url::parse(&WEBAUTHN_RP_ORIGIN).unwrap()
+}
+
+// Signs up and logs in without 2FA, leaving the auth cookie in the app's cookie jar
+async fn signup_and_login(app: &TestApp, email: &str) {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Runs the registration ceremony for the logged-in user with a software authenticator
+async fn register_passkey(app: &TestApp) -> Authenticator {
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let response = app.post_passkey_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let challenge = response
+        .json::<CreationChallengeResponse>()
+        .await
+        .expect("Could not deserialize response body to CreationChallengeResponse");
+
+    let credential = authenticator
+        .do_registration(origin(), challenge)
+        .expect("Authenticator failed to register");
+
+    let response = app.post_passkey_register_finish(&credential).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    authenticator
+}
+
+async fn start_login(app: &TestApp, body: serde_json::Value) -> PasskeyLoginChallenge {
+    let response = app.post_passkey_login_start(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeyLoginChallenge>()
+        .await
+        .expect("Could not deserialize response body to PasskeyLoginChallenge")
+}
+
+// Answers a login challenge with the authenticator
+fn sign_in(authenticator: &mut Authenticator, challenge: PasskeyLoginChallenge) -> serde_json::Value {
+    let credential = authenticator
+        .do_authentication(origin(), challenge.challenge)
+        .expect("Authenticator failed to authenticate");
+
+    serde_json::json!({
+        "ceremonyId": challenge.ceremony_id,
+        "credential": credential,
+    })
+}
+
+#[tokio::test]
+async fn should_return_400_if_registering_without_jwt_cookie() {
+    let app = TestApp::new().await;
+
+    let response = app.post_passkey_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_login_with_passkey_instead_of_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+    let mut authenticator = register_passkey(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let challenge = start_login(&app, serde_json::json!({ "email": email })).await;
+    let response = app
+        .post_passkey_login_finish(&sign_in(&mut authenticator, challenge))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_has_no_passkey() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_challenge_is_answered_twice() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+    let mut authenticator = register_passkey(&app).await;
+
+    let challenge = start_login(&app, serde_json::json!({ "email": email })).await;
+    let answer = sign_in(&mut authenticator, challenge);
+
+    let response = app.post_passkey_login_finish(&answer).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&answer).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_signed_by_another_authenticator() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app).await;
+
+    let other_email = get_random_email();
+    signup_and_login(&app, &other_email).await;
+    let mut other_authenticator = register_passkey(&app).await;
+
+    // The challenge only allows the first user's passkey
+    let challenge = start_login(&app, serde_json::json!({ "email": email })).await;
+    let credential = other_authenticator.do_authentication(origin(), challenge.challenge);
+    assert!(credential.is_err());
+}
+
+#[tokio::test]
+async fn should_complete_2fa_login_with_passkey() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    let recovery_codes = response
+        .json::<SignUpResponse>()
+        .await
+        .unwrap()
+        .recovery_codes
+        .unwrap();
+
+    let login = || async {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 206);
+
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id
+    };
+
+    // Get a session the first time round with a recovery code, to register the passkey
+    let login_attempt_id = login().await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let mut authenticator = register_passkey(&app).await;
+    app.post_logout().await;
+
+    let login_attempt_id = login().await;
+    let challenge = start_login(
+        &app,
+        serde_json::json!({ "email": email, "loginAttemptId": login_attempt_id }),
+    )
+    .await;
+    let response = app
+        .post_passkey_login_finish(&sign_in(&mut authenticator, challenge))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The login attempt has been used up by the passkey
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[1],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_login_attempt() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app).await;
+
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
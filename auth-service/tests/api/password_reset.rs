@@ -0,0 +1,201 @@
+use crate::helpers::{get_cookie, get_random_email, TestApp};
+use auth_service::domain::Email;
+use auth_service::utils::auth::generate_password_reset_token;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+// Ask for a reset link and take the token out of the email it arrives in
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sent = app
+        .email_client
+        .wait_for_email(email, "Reset your password")
+        .await;
+    sent.content
+        .split("?token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("No reset link in email")
+        .to_owned()
+}
+
+async fn reset_password(app: &TestApp, token: &str, new_password: &str) -> u16 {
+    app.post_reset_password(&serde_json::json!({
+        "token": token,
+        "newPassword": new_password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+async fn should_reset_password(app: TestApp) {
+    let email = app.signup(false).await;
+    let response = app.login(&email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let old_token = get_cookie(&response, JWT_COOKIE_NAME).unwrap();
+
+    let token = request_reset_token(&app, &email).await;
+    assert_eq!(reset_password(&app, &token, "new-password").await, 200);
+
+    // Whoever knew the old password is logged out, and cannot log in again with it
+    assert_eq!(app.verify_token(&old_token).await, 401);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 400);
+    let response = app.login(&email, "password123").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&email, "new-password").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        app.verify_token(&get_cookie(&response, JWT_COOKIE_NAME).unwrap())
+            .await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_reset_password() {
+    should_reset_password(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_reset_password_with_sqlite() {
+    should_reset_password(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_account_exists() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let unknown = get_random_email();
+
+    let known_response = app
+        .post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    let unknown_response = app
+        .post_forgot_password(&serde_json::json!({ "email": unknown }))
+        .await;
+
+    assert_eq!(known_response.status().as_u16(), 200);
+    assert_eq!(unknown_response.status().as_u16(), 200);
+    assert_eq!(
+        known_response.text().await.unwrap(),
+        unknown_response.text().await.unwrap()
+    );
+
+    app.email_client
+        .wait_for_email(&email, "Reset your password")
+        .await;
+    assert!(app.email_client.sent_to(&unknown).is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_forgot_password(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_reset_password(&serde_json::json!({ "token": "token" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn should_only_accept_token_once() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    assert_eq!(reset_password(&app, &token, "new-password").await, 200);
+    assert_eq!(reset_password(&app, &token, "other-password").await, 401);
+
+    let response = app.login(&email, "new-password").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_only_accept_token_once_when_used_concurrently() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let (first, second) = tokio::join!(
+        reset_password(&app, &token, "first-password"),
+        reset_password(&app, &token, "second-password"),
+    );
+    let mut statuses = [first, second];
+    statuses.sort();
+    assert_eq!(statuses, [200, 401]);
+
+    let (set, refused) = if first == 200 {
+        ("first-password", "second-password")
+    } else {
+        ("second-password", "first-password")
+    };
+    assert_eq!(app.login(&email, set).await.status().as_u16(), 200);
+    assert_eq!(app.login(&email, refused).await.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_refuse_earlier_tokens_after_reset() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let earlier = request_reset_token(&app, &email).await;
+    let later = generate_password_reset_token(&Email::parse(&email).unwrap()).unwrap();
+    assert_eq!(reset_password(&app, &later, "new-password").await, 200);
+
+    assert_eq!(reset_password(&app, &earlier, "other-password").await, 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_too_weak() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    assert_eq!(reset_password(&app, &token, "short").await, 400);
+
+    // The token is not used up by the failed attempt
+    assert_eq!(reset_password(&app, &token, "new-password").await, 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let response = app.login(&email, "password123").await;
+    let access_token = get_cookie(&response, JWT_COOKIE_NAME).unwrap();
+
+    // An access token does not pass for a reset token
+    for token in ["invalid", access_token.as_str()] {
+        assert_eq!(reset_password(&app, token, "new-password").await, 401);
+    }
+
+    let unknown =
+        generate_password_reset_token(&Email::parse(&get_random_email()).unwrap()).unwrap();
+    assert_eq!(reset_password(&app, &unknown, "new-password").await, 401);
+}
+
+#[tokio::test]
+async fn should_not_accept_reset_token_as_access_token() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    assert_eq!(app.verify_token(&token).await, 401);
+}
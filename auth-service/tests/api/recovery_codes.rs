@@ -0,0 +1,137 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::{ErrorResponse, RecoveryCodesResponse, SignUpResponse, TwoFactorAuthResponse};
+
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<SignUpResponse>()
+        .await
+        .expect("Could not deserialize response body to SignUpResponse")
+        .recovery_codes
+        .expect("No recovery codes returned")
+}
+
+// Logs in and answers the 2FA challenge with `code`
+async fn login_with_code(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn should_accept_each_recovery_code_once() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let codes = signup_with_2fa(&app, &email).await;
+
+    let response = login_with_code(&app, &email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let response = login_with_code(&app, &email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes are accepted however the user types them back
+    let response = login_with_code(&app, &email, &codes[1].to_uppercase().replace('-', "")).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_recovery_code() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+
+    let other_codes = signup_with_2fa(&app, &get_random_email()).await;
+
+    let response = login_with_code(&app, &email, &other_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_400_if_2fa_is_not_enabled() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "2FA is not enabled"
+    );
+}
+
+#[tokio::test]
+async fn should_invalidate_old_codes_when_regenerated() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &email).await;
+
+    let response = login_with_code(&app, &email, &old_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(new_codes.len(), old_codes.len());
+
+    let response = login_with_code(&app, &email, &old_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login_with_code(&app, &email, &new_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
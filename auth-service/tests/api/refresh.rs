@@ -0,0 +1,152 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::data_stores::RefreshTokenStoreError;
+use auth_service::domain::RefreshToken;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use auth_service::ErrorResponse;
+use reqwest::Url;
+
+// Sign up and log in, returning the refresh token the login set
+async fn login(app: &TestApp) -> String {
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    refresh_cookie(&response).expect("No refresh cookie found")
+}
+
+fn refresh_cookie(response: &reqwest::Response) -> Option<String> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+// Put an earlier refresh token back into the client's cookie jar
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Strict; Path=/", REFRESH_COOKIE_NAME, token),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_set_refresh_cookie_on_login() {
+    let app = TestApp::new().await;
+
+    let token = login(&app).await;
+
+    assert!(RefreshToken::parse(token).is_ok());
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let app = TestApp::new().await;
+    let token = login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let rotated = refresh_cookie(&response).expect("No refresh cookie found");
+    assert_ne!(rotated, token);
+
+    // The rotated token can be traded in turn
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_refresh_token() {
+    let app = TestApp::new().await;
+
+    for token in ["invalid", RefreshToken::generate().as_ref()] {
+        set_refresh_cookie(&app, token);
+
+        let response = app.post_refresh().await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid token".to_owned()
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_when_rotated_token_is_reused() {
+    let app = TestApp::new().await;
+    let stolen = login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rotated = refresh_cookie(&response).expect("No refresh cookie found");
+
+    // Replaying the already-rotated token is refused...
+    set_refresh_cookie(&app, &stolen);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // ...and takes the token it was rotated into down with it
+    set_refresh_cookie(&app, &rotated);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let app = TestApp::new().await;
+    let token = login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = RefreshToken::parse(token).expect("Invalid refresh token");
+    assert_eq!(
+        app.refresh_token_store.write().await.use_token(&token).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+}
+
+#[tokio::test]
+async fn should_rotate_refresh_token_with_sqlite_store() {
+    let app = TestApp::new_with_sqlite().await;
+    let stolen = login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &stolen);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,101 @@
+use crate::helpers::{get_random_email, TestApp, TEST_CLIENT_ID, TEST_CLIENT_SECRET};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+
+const CLIENT: Option<(&str, &str)> = Some((TEST_CLIENT_ID, TEST_CLIENT_SECRET));
+
+// Sign up and log in, returning the access and refresh tokens the login set
+async fn login(app: &TestApp) -> (String, String) {
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_owned())
+            .expect("Missing login cookie")
+    };
+
+    (cookie(JWT_COOKIE_NAME), cookie(REFRESH_COOKIE_NAME))
+}
+
+#[tokio::test]
+async fn should_revoke_access_token() {
+    for token_type_hint in [None, Some("access_token"), Some("refresh_token")] {
+        let app = TestApp::new().await;
+        let (access_token, _) = login(&app).await;
+
+        let response = app
+            .post_revoke(&access_token, token_type_hint, CLIENT)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": access_token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token() {
+    for token_type_hint in [None, Some("access_token"), Some("refresh_token")] {
+        let app = TestApp::new().await;
+        let (_, refresh_token) = login(&app).await;
+
+        let response = app
+            .post_revoke(&refresh_token, token_type_hint, CLIENT)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        // The client's cookie jar still holds the revoked refresh token
+        let response = app.post_refresh().await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_for_unknown_token() {
+    let app = TestApp::new().await;
+
+    for token in ["invalid", "a".repeat(43).as_str()] {
+        let response = app.post_revoke(token, None, CLIENT).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_credentials() {
+    let app = TestApp::new().await;
+    let (access_token, _) = login(&app).await;
+
+    for client in [None, Some((TEST_CLIENT_ID, "wrong-secret"))] {
+        let response = app.post_revoke(&access_token, None, client).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // No ADMIN_TOKEN is configured for the tests, so no admin token is accepted
+    let response = app.post_revoke_as_admin(&access_token, "admin-token").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,173 @@
+use crate::helpers::{get_cookie, TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use auth_service::SessionsResponse;
+use reqwest::header::USER_AGENT;
+
+// Log `email` in from a device known by `user_agent`, returning the access and
+// refresh tokens of the new session. The app's cookies become this session's.
+async fn login(app: &TestApp, email: &str, user_agent: &str) -> (String, String) {
+    let response = app
+        .http_client
+        .post(format!("{}/login", app.address))
+        .header(USER_AGENT, user_agent)
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie = |name| get_cookie(&response, name).expect("No cookie found");
+
+    (cookie(JWT_COOKIE_NAME), cookie(REFRESH_COOKIE_NAME))
+}
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+async fn should_list_every_session(app: TestApp) {
+    let email = app.signup(false).await;
+    login(&app, &email, "first-device").await;
+    login(&app, &email, "second-device").await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 2);
+
+    let current: Vec<_> = sessions.iter().filter(|session| session.current).collect();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0].user_agent.as_deref(), Some("second-device"));
+    assert!(sessions
+        .iter()
+        .any(|session| session.user_agent.as_deref() == Some("first-device")));
+    for session in &sessions {
+        assert_eq!(session.ip.as_deref(), Some("127.0.0.1"));
+        assert!(session.created_at <= session.last_seen_at);
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_and_list_every_session() {
+    should_list_every_session(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_list_every_session_with_sqlite() {
+    should_list_every_session(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_not_list_sessions_of_other_users() {
+    let app = TestApp::new().await;
+    let other = app.signup(false).await;
+    login(&app, &other, "other-device").await;
+
+    let email = app.signup(false).await;
+    login(&app, &email, "device").await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].user_agent.as_deref(), Some("device"));
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    assert_eq!(app.delete_session("id").await.status().as_u16(), 400);
+}
+
+// The other device is logged out; the one asking stays logged in
+async fn should_revoke_other_session(app: TestApp) {
+    let email = app.signup(false).await;
+    let (first_token, first_refresh_token) = login(&app, &email, "first-device").await;
+    let (second_token, _) = login(&app, &email, "second-device").await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    let first = sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("No other session");
+
+    let response = app.delete_session(&first.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME || !cookie.value().is_empty()));
+
+    assert_eq!(app.verify_token(&first_token).await, 401);
+    assert_eq!(app.verify_token(&second_token).await, 200);
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    app.set_cookie(REFRESH_COOKIE_NAME, &first_refresh_token);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_other_session() {
+    should_revoke_other_session(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_other_session_with_sqlite() {
+    should_revoke_other_session(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_log_out_when_revoking_current_session() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let (token, _) = login(&app, &email, "device").await;
+
+    let current = get_sessions(&app).await.sessions.remove(0);
+    let response = app.delete_session(&current.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(cookie.value().is_empty());
+
+    assert_eq!(app.verify_token(&token).await, 401);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_404_for_session_of_other_user() {
+    let app = TestApp::new().await;
+    let other = app.signup(false).await;
+    let (other_token, _) = login(&app, &other, "other-device").await;
+    let other_session = get_sessions(&app).await.sessions.remove(0);
+
+    let email = app.signup(false).await;
+    login(&app, &email, "device").await;
+
+    let response = app.delete_session(&other_session.id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(app.verify_token(&other_token).await, 200);
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_session() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    login(&app, &email, "device").await;
+
+    for id in ["not-a-session", &uuid::Uuid::new_v4().to_string()] {
+        let response = app.delete_session(id).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
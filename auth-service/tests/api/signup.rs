@@ -1,4 +1,5 @@
 use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::RECOVERY_CODE_COUNT;
 use auth_service::{ErrorResponse, SignUpResponse};
 
 #[tokio::test]
@@ -46,11 +47,37 @@ async fn should_return_201_if_successful() {
     let response = app.post_signup(&json).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    let body = response
+        .json::<SignUpResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
+
+    // Assert that we are getting the correct response body!
+    assert_eq!(body.message, "User created successfully!");
+    // The user asked for 2FA, so they get their recovery codes up front
+    assert_eq!(
+        body.recovery_codes.map(|codes| codes.len()),
+        Some(RECOVERY_CODE_COUNT)
+    );
+}
+
+#[tokio::test]
+async fn should_not_return_recovery_codes_without_2fa() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "a_password",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
     let expected_response = SignUpResponse {
         message: "User created successfully!".to_owned(),
+        recovery_codes: None,
     };
-
-    // Assert that we are getting the correct response body!
     assert_eq!(
         response
             .json::<SignUpResponse>()
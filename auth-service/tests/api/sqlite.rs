@@ -0,0 +1,99 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::Email;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::TwoFactorAuthResponse;
+
+#[tokio::test]
+async fn should_signup_login_and_logout() {
+    let app = TestApp::new_with_sqlite().await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token.value(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_complete_2fa_login() {
+    let app = TestApp::new_with_sqlite().await;
+
+    let email = Email::parse(&get_random_email()).unwrap();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email.as_ref(),
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email.as_ref(),
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let content = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let entry = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("get login attempt & code");
+    assert_eq!(entry.login_attempt_id.as_ref(), content.login_attempt_id);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email.as_ref(),
+            "loginAttemptId": entry.login_attempt_id.as_ref(),
+            "2FACode": entry.code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
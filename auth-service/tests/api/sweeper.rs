@@ -0,0 +1,18 @@
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_return_400_if_admin_token_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_sweeper_metrics(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_for_wrong_admin_token() {
+    let app = TestApp::new().await;
+
+    // No ADMIN_TOKEN is configured for the tests, so no token is accepted
+    let response = app.get_sweeper_metrics(Some("admin-token")).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
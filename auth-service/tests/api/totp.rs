@@ -0,0 +1,186 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::{RECOVERY_CODE_COUNT, TOTP_SKEW_STEPS, TOTP_STEP_SECONDS};
+use auth_service::{
+    EnrollTotpResponse, ErrorResponse, RecoveryCodesResponse, TwoFactorAuthResponse,
+};
+use chrono::Utc;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// What the user's authenticator app would show at `time`
+fn authenticator_code(secret: &str, time: u64) -> String {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        TOTP_SKEW_STEPS as u8,
+        TOTP_STEP_SECONDS,
+        Secret::Encoded(secret.to_owned()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap()
+    .generate(time)
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn enroll(app: &TestApp) -> EnrollTotpResponse {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "2FACode": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_otpauth_uri_and_qr_code() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let enrollment = enroll(&app).await;
+
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment
+        .otpauth_uri
+        .contains(&format!("secret={}", enrollment.secret)));
+    assert!(enrollment.qr_code_svg.contains("<svg"));
+}
+
+#[tokio::test]
+async fn should_return_400_if_confirmed_before_enrolling() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "2FACode": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "TOTP enrollment not started"
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirmation_code_is_wrong() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let enrollment = enroll(&app).await;
+    let now = Utc::now().timestamp() as u64;
+    let stale = now - (TOTP_SKEW_STEPS + 2) * TOTP_STEP_SECONDS;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({
+            "2FACode": authenticator_code(&enrollment.secret, stale)
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Until a code is confirmed, logging in keeps working without a second factor
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_login_with_authenticator_code_and_reject_replay() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let enrollment = enroll(&app).await;
+    let now = Utc::now().timestamp() as u64;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({
+            "2FACode": authenticator_code(&enrollment.secret, now)
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login = || async {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 206);
+
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id
+    };
+
+    // The confirmation code's time step has been used up, so log in with the next one
+    let code = authenticator_code(&enrollment.secret, now + TOTP_STEP_SECONDS);
+    let login_attempt_id = login().await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = login().await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -1,7 +1,21 @@
 use crate::helpers::TestApp;
 use auth_service::domain::data_stores::{LoginAttemptId, TwoFACode};
 use auth_service::domain::Email;
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, TWO_FA_MAX_ATTEMPTS};
+use auth_service::ErrorResponse;
+
+// The user a 2FA code is put in the store for, as only users who signed up can be logged in
+async fn signup(app: &TestApp, email: &Email) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email.as_ref(),
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
 // #[tokio::test]
 // async fn verify_2fa_is_successful() {
 //     let app = TestApp::new().await;
@@ -99,6 +113,7 @@ async fn should_return_200_for_correct_credentials() {
     let login_attempt = LoginAttemptId::default();
     let code = TwoFACode::default();
     let email = Email::parse("user@example.com").expect("setup email");
+    signup(&app, &email).await;
 
     app.two_fa_code_store
         .write()
@@ -131,6 +146,7 @@ async fn should_return_401_for_calling_twice() {
     let login_attempt = LoginAttemptId::default();
     let code = TwoFACode::default();
     let email = Email::parse("user@example.com").expect("setup email");
+    signup(&app, &email).await;
 
     app.two_fa_code_store
         .write()
@@ -152,3 +168,88 @@ async fn should_return_401_for_calling_twice() {
     let response = app.post_verify_2fa(&test_case).await;
     assert_eq!(response.status().as_u16(), 401);
 }
+
+#[tokio::test]
+async fn should_return_429_and_drop_login_attempt_after_too_many_wrong_codes() {
+    let app = TestApp::new().await;
+
+    let login_attempt = LoginAttemptId::default();
+    let code = TwoFACode::parse("ABC123".to_owned()).expect("setup code");
+    let email = Email::parse("user@example.com").expect("setup email");
+
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(email.clone(), login_attempt.clone(), code.clone())
+        .await
+        .expect("to insert 2FA Code");
+
+    let wrong_code = serde_json::json!({
+        "email": email.to_string(),
+        "loginAttemptId": login_attempt.as_ref().to_string(),
+        "2FACode": "000000",
+    });
+
+    for _ in 1..*TWO_FA_MAX_ATTEMPTS {
+        let response = app.post_verify_2fa(&wrong_code).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_verify_2fa(&wrong_code).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many 2FA attempts".to_owned()
+    );
+
+    // the correct code no longer works once the login attempt is gone
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email.to_string(),
+            "loginAttemptId": login_attempt.as_ref().to_string(),
+            "2FACode": code.as_ref().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_not_count_guesses_against_another_login_attempt() {
+    let app = TestApp::new().await;
+
+    let login_attempt = LoginAttemptId::default();
+    let code = TwoFACode::default();
+    let email = Email::parse("user@example.com").expect("setup email");
+    signup(&app, &email).await;
+
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(email.clone(), login_attempt.clone(), code.clone())
+        .await
+        .expect("to insert 2FA Code");
+
+    for _ in 0..*TWO_FA_MAX_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email.to_string(),
+                "loginAttemptId": LoginAttemptId::default().as_ref().to_string(),
+                "2FACode": "000000",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email.to_string(),
+            "loginAttemptId": login_attempt.as_ref().to_string(),
+            "2FACode": code.as_ref().to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
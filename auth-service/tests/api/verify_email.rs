@@ -0,0 +1,236 @@
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::Email;
+use auth_service::utils::auth::generate_password_reset_token;
+use chrono::Utc;
+use std::time::Duration;
+
+const SUBJECT: &str = "Verify your email address";
+
+// The token in the latest verification link sent to `email`
+async fn verification_token(app: &TestApp, email: &str) -> String {
+    let sent = app.email_client.wait_for_email(email, SUBJECT).await;
+    sent.content
+        .split("?token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("No verification link in email")
+        .to_owned()
+}
+
+async fn verify_email(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_email(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+async fn is_verified(app: &TestApp, email: &str) -> bool {
+    app.user_store
+        .read()
+        .await
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .unwrap()
+        .email_verified
+}
+
+fn verification_emails(app: &TestApp, email: &str) -> usize {
+    app.email_client
+        .sent_to(email)
+        .iter()
+        .filter(|sent| sent.subject == SUBJECT)
+        .count()
+}
+
+// Let the last verification email to `email` appear to have been sent a while ago
+async fn backdate_verification_email(app: &TestApp, email: &str) {
+    app.user_store
+        .write()
+        .await
+        .set_verification_sent_at(&Email::parse(email).unwrap(), Utc::now().timestamp() - 3600)
+        .await
+        .unwrap();
+}
+
+async fn should_verify_email(app: TestApp) {
+    let email = app.signup(false).await;
+    let token = verification_token(&app, &email).await;
+    assert!(!is_verified(&app, &email).await);
+
+    assert_eq!(verify_email(&app, &token).await, 200);
+    assert!(is_verified(&app, &email).await);
+}
+
+#[tokio::test]
+async fn should_return_200_and_verify_email() {
+    should_verify_email(TestApp::new().await).await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_verify_email_with_sqlite() {
+    should_verify_email(TestApp::new_with_sqlite().await).await;
+}
+
+#[tokio::test]
+async fn should_only_accept_token_once() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let token = verification_token(&app, &email).await;
+
+    assert_eq!(verify_email(&app, &token).await, 200);
+    assert_eq!(verify_email(&app, &token).await, 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+
+    // A token for another purpose does not pass for a verification token
+    let reset_token = generate_password_reset_token(&Email::parse(&email).unwrap()).unwrap();
+    for token in ["invalid", reset_token.as_str()] {
+        assert_eq!(verify_email(&app, token).await, 401);
+    }
+    assert!(!is_verified(&app, &email).await);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_verify_email(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({}))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn should_allow_unverified_login_unless_required() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_403_for_unverified_login_if_required() {
+    let app = TestApp::new_requiring_verified_email().await;
+    let email = app.signup(false).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response.cookies().next().is_none());
+
+    let token = verification_token(&app, &email).await;
+    assert_eq!(verify_email(&app, &token).await, 200);
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_not_send_2fa_code_for_unverified_login_if_required() {
+    let app = TestApp::new_requiring_verified_email().await;
+    let email = app.signup(true).await;
+    verification_token(&app, &email).await;
+
+    assert_eq!(
+        app.login(&email, "password123").await.status().as_u16(),
+        403
+    );
+    assert_eq!(app.email_client.sent_to(&email).len(), 1);
+}
+
+#[tokio::test]
+async fn should_still_check_password_of_unverified_login() {
+    let app = TestApp::new_requiring_verified_email().await;
+    let email = app.signup(false).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "wrong-password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_resend_verification_email() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    let first = verification_token(&app, &email).await;
+    backdate_verification_email(&app, &email).await;
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second = verification_token(&app, &email).await;
+    assert_ne!(first, second);
+    assert_eq!(verify_email(&app, &second).await, 200);
+    assert!(is_verified(&app, &email).await);
+}
+
+#[tokio::test]
+async fn should_throttle_resends() {
+    let app = TestApp::new().await;
+    let email = app.signup(false).await;
+    verification_token(&app, &email).await;
+
+    // The signup email was only just sent
+    for _ in 0..3 {
+        let response = app
+            .post_resend_verification_email(&serde_json::json!({ "email": email }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(verification_emails(&app, &email), 1);
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_account_exists_or_is_verified() {
+    let app = TestApp::new().await;
+    let verified = app.signup(false).await;
+    let token = verification_token(&app, &verified).await;
+    assert_eq!(verify_email(&app, &token).await, 200);
+    backdate_verification_email(&app, &verified).await;
+    let unknown = get_random_email();
+
+    for email in [&verified, &unknown] {
+        let response = app
+            .post_resend_verification_email(&serde_json::json!({ "email": email }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), "");
+    }
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(verification_emails(&app, &verified), 1);
+    assert!(app.email_client.sent_to(&unknown).is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}